@@ -0,0 +1,97 @@
+//! The `/apply [n]` command, applying the unified diff of the last response
+//! to the files of the `file_bundles`.
+
+use crate::args::OutputMode;
+use crate::utils::cli::{confirm, print_content, print_msg, txt_diff, MsgLevel};
+use crate::{Error, Result};
+use ai_buddy::code::CodeBlock;
+use ai_buddy::{Buddy, ChatResponse};
+use console::style;
+
+/// Applies the code block `num` (1-based) of `res`, or all its diff blocks when `None`.
+///
+/// The changes are checked and previewed first, and written only after confirmation.
+pub fn apply_patch(
+	buddy: &Buddy,
+	res: Option<&ChatResponse>,
+	num: Option<usize>,
+	output: OutputMode,
+) -> Result<()> {
+	let res = res.ok_or(Error::NoResponseYet)?;
+	let blocks = res.code_blocks();
+
+	let patch = match num {
+		Some(num) => {
+			let block = num.checked_sub(1).and_then(|idx| blocks.get(idx)).ok_or(
+				Error::CodeBlockNotFound {
+					num,
+					count: blocks.len(),
+				},
+			)?;
+			block.content.clone()
+		}
+		None => {
+			let diffs: Vec<&str> = blocks
+				.iter()
+				.filter(|b| is_diff(b))
+				.map(|b| b.content.as_str())
+				.collect();
+			if diffs.is_empty() {
+				return Err(Error::NoPatchInResponse);
+			}
+			diffs.join("\n")
+		}
+	};
+
+	// -- Check and preview
+	let changes = buddy.check_patch(&patch)?;
+	for change in changes.iter() {
+		let title = match change.old_content {
+			Some(_) => "modified",
+			None => "new file",
+		};
+		let header = format!(
+			"\n{} {}",
+			style(format!("[{}] {}", change.bundle_name, change.rel_path)).bold(),
+			style(format!("({title})")).dim()
+		);
+		print_content(output, header);
+		let old = change.old_content.as_deref().unwrap_or_default();
+		let old_name = match change.old_content {
+			Some(_) => format!("a/{}", change.rel_path),
+			None => "/dev/null".to_string(),
+		};
+		let new_name = format!("b/{}", change.rel_path);
+		let diff = txt_diff(old, &change.new_content, &old_name, &new_name);
+		print_content(output, diff);
+	}
+
+	// -- Apply
+	if !confirm(
+		output,
+		&format!("Apply the changes to {} file(s)?", changes.len()),
+	)? {
+		print_msg(output, MsgLevel::Warn, "Not applied");
+		return Ok(());
+	}
+	let backup_dir = buddy.apply_changes(&changes)?;
+	let msg = format!(
+		"Applied to {} file(s), backups in {} (/rf to re-upload the files)",
+		changes.len(),
+		backup_dir.to_string_lossy()
+	);
+	print_msg(output, MsgLevel::Success, msg);
+
+	Ok(())
+}
+
+/// Whether a code block is a unified diff (by language, or by content).
+fn is_diff(block: &CodeBlock) -> bool {
+	match block.lang.as_deref() {
+		Some("diff" | "patch" | "udiff") => true,
+		_ => {
+			block.content.starts_with("--- ")
+				&& block.content.lines().any(|l| l.starts_with("@@"))
+		}
+	}
+}
@@ -0,0 +1,123 @@
+//! Command line arguments of the `buddy` binary.
+
+use ai_buddy::InitOptions;
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Personal AI Assistant CLI")]
+pub struct Args {
+	#[command(subcommand)]
+	pub cmd: Option<Command>,
+
+	/// The buddy directory (with the buddy.toml).
+	#[arg(short, long, global = true, default_value = "buddy")]
+	pub dir: String,
+
+	/// Name of the conversation to load or create (default conversation if absent).
+	#[arg(short, long, global = true)]
+	pub conv: Option<String>,
+
+	/// Delete and recreate the assistant (and its files).
+	#[arg(long, global = true)]
+	pub recreate_asst: bool,
+
+	/// Start a new conversation (deletes the current one).
+	#[arg(long, global = true)]
+	pub recreate_conv: bool,
+
+	/// Do not bundle and upload the file bundles at startup.
+	#[arg(long, global = true)]
+	pub no_upload: bool,
+
+	/// Overrides the buddy.toml model for the runs of this invocation
+	/// (the assistant model is not changed).
+	#[arg(short, long, global = true)]
+	pub model: Option<String>,
+
+	/// Watch the instructions and the bundle sources, and re-upload them on changes.
+	#[arg(short, long)]
+	pub watch: bool,
+
+	/// Only print warnings and errors.
+	#[arg(short, long, global = true, conflicts_with = "verbose")]
+	pub quiet: bool,
+
+	/// Print more details (e.g., assistant and file ids).
+	#[arg(short, long, global = true)]
+	pub verbose: bool,
+
+	/// Output format. With `json`, the answers are JSON objects on stdout,
+	/// and the events and messages are JSON lines on stderr.
+	#[arg(short, long, global = true, value_enum, default_value_t = OutputMode::Text)]
+	pub output: OutputMode,
+
+	/// When to use colors.
+	#[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+	pub color: ColorMode,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Ask one question and print only the answer (piped stdin is attached as context).
+	Ask {
+		/// The question (e.g., `cat error.log | buddy ask "explain this"`).
+		question: String,
+	},
+	/// Show what the startup would change (assistant, instructions, conversation,
+	/// and files), without changing anything (`--recreate-asst` for what `/r` would do).
+	Plan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputMode {
+	Text,
+	Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+	Never,
+	Auto,
+	Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+	Quiet,
+	Normal,
+	Verbose,
+}
+
+impl Args {
+	pub fn verbosity(&self) -> Verbosity {
+		match (self.quiet, self.verbose) {
+			(true, _) => Verbosity::Quiet,
+			(_, true) => Verbosity::Verbose,
+			_ => Verbosity::Normal,
+		}
+	}
+
+	/// The `InitOptions` for the first `Buddy::init_from_dir`.
+	pub fn init_options(&self) -> InitOptions {
+		InitOptions {
+			recreate_asst: self.recreate_asst,
+			upload_files: !self.no_upload,
+			model: self.model.clone(),
+			// Note: In JSON mode, stderr only has JSON lines.
+			run_progress: self.output == OutputMode::Text,
+		}
+	}
+
+	/// Applies the `--color` mode to the console crate (used by dialoguer as well).
+	pub fn apply_color(&self) {
+		let enabled = match self.color {
+			ColorMode::Never => Some(false),
+			ColorMode::Always => Some(true),
+			ColorMode::Auto => None,
+		};
+		if let Some(enabled) = enabled {
+			console::set_colors_enabled(enabled);
+			console::set_colors_enabled_stderr(enabled);
+		}
+	}
+}
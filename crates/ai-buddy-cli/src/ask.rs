@@ -0,0 +1,59 @@
+//! The non-interactive `buddy ask "question"` mode.
+//!
+//! Only the answer goes to stdout, so that it can be used in scripts, git hooks, or editor commands.
+//! All the progress (events, run polling) goes to stderr.
+
+use crate::args::{Args, OutputMode};
+use crate::utils::cli::txt_res;
+use crate::{event_printer, load_conv, Result};
+use ai_buddy::event::EventBus;
+use ai_buddy::Buddy;
+use console::Term;
+use std::io::{self, IsTerminal, Read};
+
+pub async fn ask(args: &Args, question: &str) -> Result<()> {
+	// -- Read the piped stdin (if any) before anything else.
+	let stdin = read_piped_stdin()?;
+
+	let event_bus = EventBus::new();
+	let _ = event_printer(&event_bus, args.verbosity(), args.output, Term::stderr())
+		.await;
+
+	let buddy =
+		Buddy::init_from_dir(&args.dir, args.init_options(), Some(event_bus))
+			.await?;
+	let conv = load_conv(&buddy, args, args.recreate_conv).await?;
+
+	let msg = match stdin {
+		Some(context) => format!("{question}\n\n```\n{context}\n```"),
+		None => question.to_string(),
+	};
+
+	// Note: There is no one to confirm here, so `BudgetConfirmRequired` is returned as an error.
+	let res = buddy.chat(&conv, &msg).await?;
+
+	match args.output {
+		// Note: Rendered only for a terminal, the raw Markdown is kept for pipes and files.
+		OutputMode::Text if io::stdout().is_terminal() => {
+			println!("{}", txt_res(&res.text))
+		}
+		OutputMode::Text => println!("{}", res.text),
+		OutputMode::Json => println!("{}", serde_json::to_string(&res)?),
+	}
+
+	Ok(())
+}
+
+/// Returns the stdin content when it is piped (not a terminal) and not empty.
+fn read_piped_stdin() -> Result<Option<String>> {
+	let mut stdin = io::stdin();
+	if stdin.is_terminal() {
+		return Ok(None);
+	}
+
+	let mut content = String::new();
+	stdin.read_to_string(&mut content)?;
+
+	let content = content.trim_end();
+	Ok((!content.is_empty()).then(|| content.to_string()))
+}
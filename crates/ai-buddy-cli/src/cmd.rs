@@ -0,0 +1,530 @@
+//! The REPL slash-commands registry.
+//!
+//! Each command declares its name, aliases, arguments, and help text in `CMD_DEFS`,
+//! which is used for both the parsing (`Cmd::from_input`) and the `/help` output.
+
+use crate::{Error, Result};
+
+// region:    --- Cmd
+
+/// Input Command from the user
+#[derive(Debug)]
+pub enum Cmd {
+	Quit,
+	Help(Option<String>),
+	Chat(String),
+	/// Compose a multiline message in the terminal.
+	Multiline,
+	/// Compose the message in `$EDITOR` (optionally pre-filled with the previous message).
+	Edit {
+		prefill_last: bool,
+	},
+	/// Save a code block of the last response to a file.
+	Save {
+		num: usize,
+		path: String,
+	},
+	/// Apply the diff code block(s) of the last response to the bundle files.
+	Apply {
+		num: Option<usize>,
+	},
+	/// Chat message with local files attached to this message only.
+	File {
+		path: String,
+		question: Option<String>,
+	},
+	RefreshAll,
+	RefreshConv,
+	/// Re-upload the instructions (keeping the conversation unless `new_conv`).
+	RefreshInst {
+		new_conv: bool,
+	},
+	/// Re-bundle and re-upload the files (keeping the conversation unless `new_conv`).
+	RefreshFiles {
+		new_conv: bool,
+	},
+	Cost,
+}
+
+impl Cmd {
+	/// Parses the user input.
+	///
+	/// - Input not starting with `/` is a chat message (`//...` sends `/...` as a chat message).
+	/// - Otherwise, the command must be in the registry, or `Error::CmdUnknown` is returned.
+	pub fn from_input(input: impl Into<String>) -> Result<Self> {
+		let input = input.into();
+
+		let Some(cmd_line) = input.strip_prefix('/') else {
+			return Ok(Self::Chat(input));
+		};
+		if cmd_line.starts_with('/') {
+			return Ok(Self::Chat(cmd_line.to_string()));
+		}
+
+		let (name, rest) = match cmd_line.split_once(char::is_whitespace) {
+			Some((name, rest)) => (name, rest.trim()),
+			None => (cmd_line, ""),
+		};
+
+		let cmd_def =
+			find_cmd_def(name).ok_or_else(|| Error::CmdUnknown(name.to_string()))?;
+		let args = cmd_def.parse_args(rest)?;
+
+		(cmd_def.build)(args)
+	}
+}
+
+// endregion: --- Cmd
+
+// region:    --- CmdDef
+
+pub struct CmdDef {
+	pub name: &'static str,
+	pub aliases: &'static [&'static str],
+	pub args: &'static [ArgDef],
+	pub help: &'static str,
+	build: fn(CmdArgs) -> Result<Cmd>,
+}
+
+pub struct ArgDef {
+	pub name: &'static str,
+	pub kind: ArgKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+	/// One required word.
+	Required,
+	/// One optional word.
+	Optional,
+	/// The rest of the line (optional), must be the last argument.
+	Rest,
+}
+
+/// The parsed arguments, in the `CmdDef.args` order (`None` when an optional one is absent).
+pub struct CmdArgs(Vec<Option<String>>);
+
+impl CmdArgs {
+	pub fn get(&self, idx: usize) -> Option<&str> {
+		self.0.get(idx).and_then(|v| v.as_deref())
+	}
+}
+
+/// Parses the optional `new` argument of the refresh commands.
+fn parse_new_conv(cmd: &'static str, args: &CmdArgs) -> Result<bool> {
+	match args.get(0) {
+		None => Ok(false),
+		Some("new") => Ok(true),
+		Some(value) => Err(Error::CmdInvalidArg {
+			cmd,
+			arg: "new",
+			value: value.to_string(),
+		}),
+	}
+}
+
+impl CmdDef {
+	fn parse_args(&self, mut rest: &str) -> Result<CmdArgs> {
+		let mut values = Vec::with_capacity(self.args.len());
+
+		for arg_def in self.args {
+			let value = match arg_def.kind {
+				ArgKind::Rest => Some(std::mem::take(&mut rest).to_string()),
+				ArgKind::Required | ArgKind::Optional => {
+					let (word, remain) = match rest.split_once(char::is_whitespace) {
+						Some((word, remain)) => (word, remain.trim_start()),
+						None => (rest, ""),
+					};
+					rest = remain;
+					Some(word.to_string())
+				}
+			}
+			.filter(|v| !v.is_empty());
+
+			if value.is_none() && arg_def.kind == ArgKind::Required {
+				return Err(Error::CmdMissingArg {
+					cmd: self.name,
+					arg: arg_def.name,
+				});
+			}
+			values.push(value);
+		}
+
+		if !rest.is_empty() {
+			return Err(Error::CmdTooManyArgs(self.name));
+		}
+
+		Ok(CmdArgs(values))
+	}
+
+	/// e.g., `/save <n> <path>`
+	pub fn usage(&self) -> String {
+		let mut usage = format!("/{}", self.name);
+		for arg in self.args {
+			match arg.kind {
+				ArgKind::Required => usage.push_str(&format!(" <{}>", arg.name)),
+				ArgKind::Optional | ArgKind::Rest => {
+					usage.push_str(&format!(" [{}]", arg.name))
+				}
+			}
+		}
+		usage
+	}
+}
+
+pub const CMD_DEFS: &[CmdDef] = &[
+	CmdDef {
+		name: "help",
+		aliases: &["h", "?"],
+		args: &[ArgDef {
+			name: "command",
+			kind: ArgKind::Optional,
+		}],
+		help: "Show this help, or the help of a command",
+		build: |args| Ok(Cmd::Help(args.get(0).map(|v| v.to_string()))),
+	},
+	CmdDef {
+		name: "ml",
+		aliases: &["paste"],
+		args: &[],
+		help: "Type or paste a multiline message (end with a line containing only `.`)",
+		build: |_| Ok(Cmd::Multiline),
+	},
+	CmdDef {
+		name: "edit",
+		aliases: &["e"],
+		args: &[ArgDef {
+			name: "last",
+			kind: ArgKind::Optional,
+		}],
+		help: "Compose the message in $EDITOR (`last` to start from the previous message)",
+		build: |args| match args.get(0) {
+			None => Ok(Cmd::Edit {
+				prefill_last: false,
+			}),
+			Some("last") => Ok(Cmd::Edit { prefill_last: true }),
+			Some(value) => Err(Error::CmdInvalidArg {
+				cmd: "edit",
+				arg: "last",
+				value: value.to_string(),
+			}),
+		},
+	},
+	CmdDef {
+		name: "file",
+		aliases: &["f"],
+		args: &[
+			ArgDef {
+				name: "path or glob",
+				kind: ArgKind::Required,
+			},
+			ArgDef {
+				name: "question",
+				kind: ArgKind::Rest,
+			},
+		],
+		help: "Attach files to a single message (e.g., /file src/**/*.rs explain the errors)",
+		build: |args| {
+			Ok(Cmd::File {
+				path: args.get(0).unwrap_or_default().to_string(),
+				question: args.get(1).map(|v| v.to_string()),
+			})
+		},
+	},
+	CmdDef {
+		name: "save",
+		aliases: &["s"],
+		args: &[
+			ArgDef {
+				name: "n",
+				kind: ArgKind::Required,
+			},
+			ArgDef {
+				name: "path",
+				kind: ArgKind::Required,
+			},
+		],
+		help: "Save the code block [n] of the last response to a file",
+		build: |args| {
+			let num = args.get(0).unwrap_or_default();
+			let num = num.parse().map_err(|_| Error::CmdInvalidArg {
+				cmd: "save",
+				arg: "n",
+				value: num.to_string(),
+			})?;
+			Ok(Cmd::Save {
+				num,
+				path: args.get(1).unwrap_or_default().to_string(),
+			})
+		},
+	},
+	CmdDef {
+		name: "apply",
+		aliases: &[],
+		args: &[ArgDef {
+			name: "n",
+			kind: ArgKind::Optional,
+		}],
+		help: "Apply the diff code block(s) of the last response to the bundle files",
+		build: |args| {
+			let num = args
+				.get(0)
+				.map(|num| {
+					num.parse().map_err(|_| Error::CmdInvalidArg {
+						cmd: "apply",
+						arg: "n",
+						value: num.to_string(),
+					})
+				})
+				.transpose()?;
+			Ok(Cmd::Apply { num })
+		},
+	},
+	CmdDef {
+		name: "q",
+		aliases: &["quit", "exit"],
+		args: &[],
+		help: "Quit",
+		build: |_| Ok(Cmd::Quit),
+	},
+	CmdDef {
+		name: "r",
+		aliases: &["ra"],
+		args: &[],
+		help: "Recreate the assistant, re-upload everything, and start a new conversation",
+		build: |_| Ok(Cmd::RefreshAll),
+	},
+	CmdDef {
+		name: "ri",
+		aliases: &[],
+		args: &[ArgDef {
+			name: "new",
+			kind: ArgKind::Optional,
+		}],
+		help: "Re-upload the instructions (`new` to also start a new conversation)",
+		build: |args| {
+			let new_conv = parse_new_conv("ri", &args)?;
+			Ok(Cmd::RefreshInst { new_conv })
+		},
+	},
+	CmdDef {
+		name: "rf",
+		aliases: &[],
+		args: &[ArgDef {
+			name: "new",
+			kind: ArgKind::Optional,
+		}],
+		help: "Re-bundle and re-upload the files (`new` to also start a new conversation)",
+		build: |args| {
+			let new_conv = parse_new_conv("rf", &args)?;
+			Ok(Cmd::RefreshFiles { new_conv })
+		},
+	},
+	CmdDef {
+		name: "rc",
+		aliases: &[],
+		args: &[],
+		help: "Start a new conversation",
+		build: |_| Ok(Cmd::RefreshConv),
+	},
+	CmdDef {
+		name: "cost",
+		aliases: &[],
+		args: &[],
+		help: "Show the token usage and cost (conversation, today, total)",
+		build: |_| Ok(Cmd::Cost),
+	},
+];
+
+pub fn find_cmd_def(name: &str) -> Option<&'static CmdDef> {
+	CMD_DEFS
+		.iter()
+		.find(|def| def.name == name || def.aliases.contains(&name))
+}
+
+/// The `/help` text, for all commands or a given one.
+pub fn help_text(cmd_name: Option<&str>) -> Result<String> {
+	if let Some(cmd_name) = cmd_name {
+		let cmd_name = cmd_name.trim_start_matches('/');
+		let def = find_cmd_def(cmd_name)
+			.ok_or_else(|| Error::CmdUnknown(cmd_name.to_string()))?;
+		return Ok(format!("  {}  {}", def.usage(), def.help));
+	}
+
+	let usages: Vec<String> = CMD_DEFS.iter().map(|def| def.usage()).collect();
+	let width = usages.iter().map(|u| u.len()).max().unwrap_or_default();
+
+	let mut lines = vec!["Commands:".to_string()];
+	for (def, usage) in CMD_DEFS.iter().zip(usages.iter()) {
+		let mut line = format!("  {usage:<width$}  {}", def.help);
+		if !def.aliases.is_empty() {
+			let aliases: Vec<String> =
+				def.aliases.iter().map(|a| format!("/{a}")).collect();
+			line.push_str(&format!(" (alias: {})", aliases.join(", ")));
+		}
+		lines.push(line);
+	}
+	lines.push(format!(
+		"  {:<width$}  Send a chat message starting with /",
+		"//..."
+	));
+
+	Ok(lines.join("\n"))
+}
+
+// endregion: --- CmdDef
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_cmd_from_input_chat_ok() -> Result<()> {
+		// -- Exec & Check
+		assert!(
+			matches!(Cmd::from_input("hello /q")?, Cmd::Chat(msg) if msg == "hello /q")
+		);
+		assert!(
+			matches!(Cmd::from_input("//q is a path")?, Cmd::Chat(msg) if msg == "/q is a path")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_from_input_aliases_ok() -> Result<()> {
+		// -- Exec & Check
+		assert!(matches!(Cmd::from_input("/exit")?, Cmd::Quit));
+		assert!(matches!(Cmd::from_input("/?")?, Cmd::Help(None)));
+		assert!(
+			matches!(Cmd::from_input("/h save")?, Cmd::Help(Some(name)) if name == "save")
+		);
+		assert!(matches!(
+			Cmd::from_input("/rf new")?,
+			Cmd::RefreshFiles { new_conv: true }
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_from_input_whitespace_ok() -> Result<()> {
+		// -- Exec & Check
+		assert!(matches!(
+			Cmd::from_input("/save  2 \t out.rs  ")?,
+			Cmd::Save { num: 2, path } if path == "out.rs"
+		));
+		assert!(matches!(
+			Cmd::from_input("/file   src/**/*.rs   explain  the errors ")?,
+			Cmd::File { path, question: Some(question) }
+				if path == "src/**/*.rs" && question == "explain  the errors"
+		));
+		assert!(matches!(
+			Cmd::from_input("/f src/main.rs")?,
+			Cmd::File { question: None, .. }
+		));
+		assert!(matches!(
+			Cmd::from_input("/apply ")?,
+			Cmd::Apply { num: None }
+		));
+		assert!(matches!(
+			Cmd::from_input("/ri  ")?,
+			Cmd::RefreshInst { new_conv: false }
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_from_input_unknown_err() -> Result<()> {
+		// -- Exec & Check
+		assert!(matches!(
+			Cmd::from_input("/foo bar"),
+			Err(crate::Error::CmdUnknown(name)) if name == "foo"
+		));
+		assert!(matches!(
+			Cmd::from_input("/"),
+			Err(crate::Error::CmdUnknown(name)) if name.is_empty()
+		));
+		// Note: The command names are case sensitive.
+		assert!(matches!(
+			Cmd::from_input("/Q"),
+			Err(crate::Error::CmdUnknown(_))
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_from_input_missing_arg_err() -> Result<()> {
+		// -- Exec & Check
+		assert!(matches!(
+			Cmd::from_input("/save 1"),
+			Err(crate::Error::CmdMissingArg {
+				cmd: "save",
+				arg: "path"
+			})
+		));
+		assert!(matches!(
+			Cmd::from_input("/save"),
+			Err(crate::Error::CmdMissingArg {
+				cmd: "save",
+				arg: "n"
+			})
+		));
+		assert!(matches!(
+			Cmd::from_input("/file   "),
+			Err(crate::Error::CmdMissingArg { cmd: "file", .. })
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_from_input_invalid_args_err() -> Result<()> {
+		// -- Exec & Check
+		assert!(matches!(
+			Cmd::from_input("/save one out.rs"),
+			Err(crate::Error::CmdInvalidArg {
+				cmd: "save",
+				arg: "n",
+				..
+			})
+		));
+		assert!(matches!(
+			Cmd::from_input("/rf old"),
+			Err(crate::Error::CmdInvalidArg { cmd: "rf", .. })
+		));
+		assert!(matches!(
+			Cmd::from_input("/q now"),
+			Err(crate::Error::CmdTooManyArgs("q"))
+		));
+		assert!(matches!(
+			Cmd::from_input("/save 1 out.rs extra"),
+			Err(crate::Error::CmdTooManyArgs("save"))
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_help_text_ok() -> Result<()> {
+		// -- Exec
+		let all = help_text(None)?;
+		let save = help_text(Some("/s"))?;
+
+		// -- Check
+		assert!(CMD_DEFS.iter().all(|def| all.contains(&def.usage())));
+		assert!(save.starts_with("  /save <n> <path>  "));
+		assert!(help_text(Some("nope")).is_err());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
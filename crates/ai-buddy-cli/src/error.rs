@@ -1,10 +1,33 @@
+use ai_buddy::ErrorKind;
 use derive_more::From;
+use serde_json::json;
 use std::io;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug, From)]
 pub enum Error {
+	// -- Cmd
+	CmdUnknown(String),
+	CmdMissingArg {
+		cmd: &'static str,
+		arg: &'static str,
+	},
+	CmdTooManyArgs(&'static str),
+	CmdInvalidArg {
+		cmd: &'static str,
+		arg: &'static str,
+		value: String,
+	},
+
+	// -- Code Blocks
+	NoResponseYet,
+	CodeBlockNotFound {
+		num: usize,
+		count: usize,
+	},
+	NoPatchInResponse,
+
 	// -- App Libs
 	#[from]
 	AIBuddy(ai_buddy::Error),
@@ -14,6 +37,10 @@ pub enum Error {
 	IO(io::Error),
 	#[from]
 	Dialoguer(dialoguer::Error),
+	#[from]
+	Readline(rustyline::error::ReadlineError),
+	#[from]
+	SerdeJson(serde_json::Error),
 }
 
 // region:    --- Error Boilerplate
@@ -22,7 +49,73 @@ impl core::fmt::Display for Error {
 		&self,
 		fmt: &mut core::fmt::Formatter,
 	) -> core::result::Result<(), core::fmt::Error> {
-		write!(fmt, "{self:?}")
+		match self {
+			Error::CmdUnknown(name) => write!(fmt, "Unknown command /{name}"),
+			Error::CmdMissingArg { cmd, arg } => {
+				write!(fmt, "Missing <{arg}> argument for /{cmd}")
+			}
+			Error::CmdTooManyArgs(cmd) => {
+				write!(fmt, "Too many arguments for /{cmd}")
+			}
+			Error::CmdInvalidArg { cmd, arg, value } => {
+				write!(fmt, "Invalid <{arg}> argument '{value}' for /{cmd}")
+			}
+			Error::NoResponseYet => write!(fmt, "No response in this session yet"),
+			Error::CodeBlockNotFound { num, count } => write!(
+				fmt,
+				"No code block [{num}] in the last response ({count} code blocks)"
+			),
+			Error::NoPatchInResponse => write!(
+				fmt,
+				"No diff code block in the last response (use /apply <n> for a given block)"
+			),
+			Error::AIBuddy(err) => write!(fmt, "{err}"),
+			Error::IO(err) => write!(fmt, "IO error: {err}"),
+			Error::Dialoguer(err) => write!(fmt, "Prompt error: {err}"),
+			Error::Readline(err) => write!(fmt, "Line editor error: {err}"),
+			Error::SerdeJson(err) => write!(fmt, "JSON error: {err}"),
+		}
+	}
+}
+
+impl Error {
+	/// The JSON error object for the `--output json` mode.
+	pub fn to_json(&self) -> serde_json::Value {
+		let kind = match self {
+			Error::AIBuddy(err) => Some(err.kind()),
+			_ => None,
+		};
+		json!({
+			"error": self.to_string(),
+			"kind": kind,
+			"hint": self.hint(),
+		})
+	}
+
+	/// A hint for the user on how to fix the error, based on its kind.
+	pub fn hint(&self) -> Option<&'static str> {
+		let Error::AIBuddy(err) = self else {
+			return None;
+		};
+		match err.kind() {
+			ErrorKind::Config => {
+				Some("Check the buddy.toml of the buddy directory.")
+			}
+			ErrorKind::Auth => Some(
+				"Set the OPENAI_API_KEY environment variable to a valid API key.",
+			),
+			ErrorKind::Network => {
+				Some("Check the network connection and try again.")
+			}
+			ErrorKind::RateLimit => Some(
+				"Rate limit or quota reached, wait a bit or check the account plan.",
+			),
+			ErrorKind::Provider => Some("The AI provider failed, try again later."),
+			ErrorKind::LocalIo => None,
+			ErrorKind::Input => {
+				Some("Check the input (e.g., ask for a patch of the current files).")
+			}
+		}
 	}
 }
 
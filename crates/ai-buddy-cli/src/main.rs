@@ -1,80 +1,134 @@
 // region:    --- Modules
 
+mod apply;
+mod args;
+mod ask;
+mod cmd;
 mod error;
+mod plan;
+mod save;
 mod utils;
 
 pub use self::error::{Error, Result};
+use crate::apply::apply_patch;
+use crate::args::{Args, Command, OutputMode, Verbosity};
+use crate::cmd::{help_text, Cmd};
+use crate::save::save_code_block;
 use crate::utils::cli::{
-	ico_check, ico_deleted_ok, ico_err, ico_uploaded, ico_uploading,
+	confirm, ico_check, ico_deleted_ok, ico_err, ico_uploaded, ico_uploading,
+	ico_warn,
 };
+use crate::utils::line_editor::LineEditor;
 use ai_buddy::event::{AisEvent, Event, EventBus};
-use ai_buddy::{Buddy, BuddyEvent};
+use ai_buddy::{
+	list_attach_files, AttachMode, Buddy, BuddyEvent, ChatResponse, Conv, ErrorKind,
+	InitOptions,
+};
+use clap::Parser;
 use console::Term;
+use std::fmt::Display;
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use textwrap::wrap;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use utils::cli::{ico_res, prompt, txt_res};
+use utils::cli::{
+	ico_res, print_content, print_msg, prompt_editor, prompt_multiline, set_quiet,
+	txt_res, txt_res_meta, txt_usage_report, MsgLevel,
+};
 
 // endregion: --- Modules
 
+/// The `/file` message when no question is given.
+const DEFAULT_FILE_QUESTION: &str = "Review the attached file(s).";
+
+/// The notes added to the kept conversation by `/ri` and `/rf`.
+const NOTE_INST_REFRESHED: &str = "Note: Your instructions were just updated. \
+	Follow the new instructions from now on.";
+const NOTE_FILES_REFRESHED: &str =
+	"Note: The knowledge files were just re-uploaded. \
+	Use their current content from now on, as the code quoted earlier in this \
+	conversation might be outdated.";
+
 #[tokio::main]
 async fn main() {
-	println!();
-	let _ = io::stdout().flush();
-
-	match start().await {
-		Ok(_) => println!("\nBye!\n"),
-		Err(e) => println!("\nError: {}\n", e),
+	let args = Args::parse();
+	args.apply_color();
+	set_quiet(args.quiet);
+
+	// -- One-shot modes (result on stdout, everything else on stderr)
+	let one_shot_res = match args.cmd.as_ref() {
+		Some(Command::Ask { question }) => Some(ask::ask(&args, question).await),
+		Some(Command::Plan) => Some(plan::plan(&args).await),
+		None => None,
+	};
+	if let Some(res) = one_shot_res {
+		if let Err(e) = res {
+			match args.output {
+				OutputMode::Text => {
+					eprintln!("{} {}", ico_err(), e);
+					if let Some(hint) = e.hint() {
+						eprintln!("  {hint}");
+					}
+				}
+				OutputMode::Json => eprintln!("{}", e.to_json()),
+			}
+			std::process::exit(1);
+		}
+		return;
 	}
-}
-
-const DEFAULT_DIR: &str = "buddy";
 
-// region:    --- Types
-
-/// Input Command from the user
-#[derive(Debug)]
-enum Cmd {
-	Quit,
-	Chat(String),
-	RefreshAll,
-	RefreshConv,
-	RefreshInst,
-	RefreshFiles,
-}
+	// -- Interactive mode
+	let output = args.output;
+	if output == OutputMode::Text {
+		println!();
+		let _ = io::stdout().flush();
+	}
 
-impl Cmd {
-	fn from_input(input: impl Into<String>) -> Self {
-		let input = input.into();
-
-		if input == "/q" {
-			Self::Quit
-		} else if input == "/r" || input == "/ra" {
-			Self::RefreshAll
-		} else if input == "/ri" {
-			Self::RefreshInst
-		} else if input == "/rf" {
-			Self::RefreshFiles
-		} else if input == "/rc" {
-			Self::RefreshConv
-		} else {
-			Self::Chat(input)
+	match (start(args).await, output) {
+		(Ok(_), OutputMode::Text) => println!("\nBye!\n"),
+		(Ok(_), OutputMode::Json) => print_msg(output, MsgLevel::Info, "Bye!"),
+		(Err(e), OutputMode::Text) => {
+			println!("\n{} {}", ico_err(), e);
+			if let Some(hint) = e.hint() {
+				println!("  {hint}");
+			}
+			println!();
+			std::process::exit(1);
+		}
+		(Err(e), OutputMode::Json) => {
+			eprintln!("{}", e.to_json());
+			std::process::exit(1);
 		}
 	}
 }
 
-// endregion: --- Types
-
-async fn start() -> Result<()> {
+async fn start(args: Args) -> Result<()> {
+	let verbosity = args.verbosity();
+	let output = args.output;
+	// Note: In JSON mode, the events go to stderr so that stdout only has the answers.
+	let event_term = match output {
+		OutputMode::Text => Term::stdout(),
+		OutputMode::Json => Term::stderr(),
+	};
 	let event_bus = EventBus::new();
 
-	let _ = event_printer(&event_bus).await;
+	let _ = event_printer(&event_bus, verbosity, output, event_term.clone()).await;
 
-	let mut buddy =
-		Buddy::init_from_dir(DEFAULT_DIR, false, Some(event_bus)).await?;
+	let mut buddy = Arc::new(
+		Buddy::init_from_dir(&args.dir, args.init_options(), Some(event_bus))
+			.await?,
+	);
+	let mut watch_task = args.watch.then(|| spawn_watch(buddy.clone(), output));
 
-	let mut conv = buddy.load_or_create_conv(false).await?;
+	let mut conv = load_conv(&buddy, &args, args.recreate_conv).await?;
+	let mut last_msg: Option<String> = None;
+	let mut last_res: Option<ChatResponse> = None;
+	let mut line_editor = LineEditor::new(
+		buddy.data_dir()?.join("history"),
+		output == OutputMode::Json,
+	)?;
 
 	loop {
 		// TODO: This sleep needs to be removed.
@@ -82,40 +136,172 @@ async fn start() -> Result<()> {
 		//       Eventually, we need to implement a "buddy.ready()" scheme or something similar.
 		sleep(Duration::from_millis(50)).await;
 
-		let input = prompt("Ask away")?;
+		let Some(input) = line_editor.read_line("Ask away")? else {
+			break;
+		};
 
-		let cmd = Cmd::from_input(input);
+		let cmd = match Cmd::from_input(input) {
+			Ok(cmd) => cmd,
+			Err(err) => {
+				print_msg(output, MsgLevel::Error, format!("{err} (see /help)"));
+				continue;
+			}
+		};
 
 		match cmd {
 			Cmd::Quit => break,
 
+			Cmd::Help(cmd_name) => match help_text(cmd_name.as_deref()) {
+				Ok(help) => print_content(output, help),
+				Err(err) => print_msg(output, MsgLevel::Error, err),
+			},
+
+			Cmd::Save { num, path } => {
+				if let Err(err) =
+					save_code_block(last_res.as_ref(), num, &path, output)
+				{
+					print_msg(output, MsgLevel::Error, err);
+				}
+			}
+
+			Cmd::Apply { num } => {
+				if let Err(err) = apply_patch(&buddy, last_res.as_ref(), num, output)
+				{
+					print_msg(output, MsgLevel::Error, err);
+				}
+			}
+
+			Cmd::File { path, question } => {
+				let files = match list_attach_files(&path) {
+					Ok(files) => files,
+					Err(err) => {
+						print_msg(output, MsgLevel::Error, err);
+						continue;
+					}
+				};
+				let msg =
+					question.unwrap_or_else(|| DEFAULT_FILE_QUESTION.to_string());
+				last_res = send_msg(&buddy, &conv, &msg, &files, output)
+					.await?
+					.or(last_res);
+				last_msg = Some(msg);
+			}
+
 			Cmd::Chat(msg) => {
-				let res = buddy.chat(&conv, &msg).await?;
-				let res = wrap(&res, 80).join("\n");
-				println!("{} {}", ico_res(), txt_res(res));
+				last_res = send_msg(&buddy, &conv, &msg, &[], output)
+					.await?
+					.or(last_res);
+				last_msg = Some(msg);
+			}
+
+			// -- The composed messages are sent as regular chat messages.
+			Cmd::Multiline => {
+				if let Some(msg) = prompt_multiline(output)? {
+					last_res = send_msg(&buddy, &conv, &msg, &[], output)
+						.await?
+						.or(last_res);
+					last_msg = Some(msg);
+				}
+			}
+
+			Cmd::Edit { prefill_last } => {
+				let content = match (prefill_last, last_msg.as_deref()) {
+					(true, Some(last_msg)) => last_msg,
+					_ => "",
+				};
+				// Note: An editor failure (e.g., invalid $EDITOR) should not end the session.
+				match prompt_editor(content) {
+					Ok(Some(msg)) => {
+						last_res = send_msg(&buddy, &conv, &msg, &[], output)
+							.await?
+							.or(last_res);
+						last_msg = Some(msg);
+					}
+					Ok(None) => print_msg(
+						output,
+						MsgLevel::Warn,
+						"Nothing sent (empty or not saved)",
+					),
+					Err(err) => print_msg(output, MsgLevel::Error, err),
+				}
 			}
 
 			Cmd::RefreshAll => {
 				let event_bus = EventBus::new();
-				let _ = event_printer(&event_bus).await;
-
-				buddy =
-					Buddy::init_from_dir(DEFAULT_DIR, true, Some(event_bus)).await?;
-				conv = buddy.load_or_create_conv(true).await?;
+				let _ =
+					event_printer(&event_bus, verbosity, output, event_term.clone())
+						.await;
+
+				let options = InitOptions {
+					recreate_asst: true,
+					upload_files: true,
+					..args.init_options()
+				};
+				buddy = Arc::new(
+					Buddy::init_from_dir(&args.dir, options, Some(event_bus))
+						.await?,
+				);
+				conv = load_conv(&buddy, &args, true).await?;
+
+				// -- Restart the watch with the new buddy
+				if let Some(task) = watch_task.take() {
+					// Note: Awaited so that the old watchers are dropped before the new ones start.
+					task.abort();
+					let _ = task.await;
+					watch_task = Some(spawn_watch(buddy.clone(), output));
+				}
 			}
 
 			Cmd::RefreshConv => {
-				conv = buddy.load_or_create_conv(true).await?;
+				conv = load_conv(&buddy, &args, true).await?;
 			}
 
-			Cmd::RefreshInst => {
-				buddy.upload_instructions().await?;
-				conv = buddy.load_or_create_conv(true).await?;
+			Cmd::RefreshInst { new_conv } => {
+				let uploaded = buddy.upload_instructions().await?;
+				if new_conv {
+					conv = load_conv(&buddy, &args, true).await?;
+				} else if uploaded {
+					buddy.add_conv_note(&conv, NOTE_INST_REFRESHED).await?;
+				}
 			}
 
-			Cmd::RefreshFiles => {
-				buddy.upload_files(true).await?;
-				conv = buddy.load_or_create_conv(true).await?;
+			Cmd::RefreshFiles { new_conv } => {
+				let count = match buddy.upload_files(true).await {
+					Err(ai_buddy::Error::BudgetConfirmRequired(exceeded)) => {
+						if !confirm(
+							output,
+							&format!("Over the {exceeded}. Upload anyway?"),
+						)? {
+							continue;
+						}
+						buddy.upload_files_confirmed(true).await?
+					}
+					Err(ai_buddy::Error::BudgetExceeded(exceeded)) => {
+						let msg = format!("Not uploaded, over the {exceeded}");
+						print_msg(output, MsgLevel::Error, msg);
+						continue;
+					}
+					Err(err @ ai_buddy::Error::SecretsFound(_)) => {
+						print_msg(output, MsgLevel::Error, err);
+						continue;
+					}
+					other => other?,
+				};
+				if new_conv {
+					conv = load_conv(&buddy, &args, true).await?;
+				} else if count > 0 {
+					buddy.add_conv_note(&conv, NOTE_FILES_REFRESHED).await?;
+				}
+			}
+
+			Cmd::Cost => {
+				let report = buddy.usage_report(&conv)?;
+				match output {
+					OutputMode::Text => println!("{}", txt_usage_report(&report)),
+					OutputMode::Json => {
+						println!("{}", serde_json::to_string(&report)?)
+					}
+				}
 			}
 		}
 	}
@@ -123,45 +309,145 @@ async fn start() -> Result<()> {
 	Ok(())
 }
 
-async fn event_printer(event_bus: &EventBus) -> Result<()> {
+/// Runs `Buddy::watch` in the background (the changes are printed by the `event_printer`).
+fn spawn_watch(buddy: Arc<Buddy>, output: OutputMode) -> JoinHandle<()> {
+	tokio::spawn(async move {
+		if let Err(err) = buddy.watch().await {
+			print_msg(output, MsgLevel::Error, format!("Watch stopped: {err}"));
+		}
+	})
+}
+
+/// Sends the chat message and prints the response.
+///
+/// Note: The budget and provider errors are printed, and do not end the session (returns `None`).
+async fn send_msg(
+	buddy: &Buddy,
+	conv: &Conv,
+	msg: &str,
+	files: &[PathBuf],
+	output: OutputMode,
+) -> Result<Option<ChatResponse>> {
+	let res = match buddy.chat_with_files(conv, msg, files).await {
+		Err(ai_buddy::Error::BudgetConfirmRequired(exceeded)) => {
+			if !confirm(output, &format!("Over the {exceeded}. Send anyway?"))? {
+				return Ok(None);
+			}
+			buddy.chat_with_files_confirmed(conv, msg, files).await
+		}
+		other => other,
+	};
+	let res = match res {
+		Err(ai_buddy::Error::BudgetExceeded(exceeded)) => {
+			let msg = format!("Not sent, over the {exceeded}");
+			print_msg(output, MsgLevel::Error, msg);
+			return Ok(None);
+		}
+		// Note: The details were already printed by the RunFailed event.
+		Err(ai_buddy::Error::RunError(_)) => return Ok(None),
+		// Provider side and attachment errors should not end the session.
+		Err(err)
+			if matches!(
+				err.kind(),
+				ErrorKind::Network
+					| ErrorKind::RateLimit
+					| ErrorKind::Provider
+					| ErrorKind::Config
+			) =>
+		{
+			print_msg(output, MsgLevel::Error, err);
+			return Ok(None);
+		}
+		other => other?,
+	};
+	match output {
+		OutputMode::Text => {
+			println!("{}\n{}", ico_res(), txt_res(&res.text));
+			println!("{}", txt_res_meta(&res));
+		}
+		OutputMode::Json => println!("{}", serde_json::to_string(&res)?),
+	}
+
+	Ok(Some(res))
+}
+
+/// Loads or creates the `--conv` conversation (or the default one).
+async fn load_conv(buddy: &Buddy, args: &Args, recreate: bool) -> Result<Conv> {
+	let conv = match args.conv.as_deref() {
+		Some(name) => buddy.load_or_create_named_conv(name, recreate).await?,
+		None => buddy.load_or_create_conv(recreate).await?,
+	};
+	Ok(conv)
+}
+
+async fn event_printer(
+	event_bus: &EventBus,
+	verbosity: Verbosity,
+	output: OutputMode,
+	term: Term,
+) -> Result<()> {
 	let mut rx = event_bus.subscribe()?;
 
-	tokio::spawn(async move {
-		let term = Term::stdout();
+	// The " (id)" suffix in verbose mode.
+	let id = move |id: &dyn Display| {
+		if verbosity == Verbosity::Verbose {
+			format!(" ({id})")
+		} else {
+			String::new()
+		}
+	};
 
+	tokio::spawn(async move {
 		loop {
 			let evt = rx.recv().await;
 			let _ = term.flush();
 
 			if let Ok(evt) = evt {
+				// In JSON mode, every event is a JSON line.
+				if output == OutputMode::Json {
+					if let Ok(line) = serde_json::to_string(&evt) {
+						let _ = term.write_line(&line);
+					}
+					continue;
+				}
+
+				// In quiet mode, only the warnings and errors are printed.
+				if verbosity == Verbosity::Quiet && !is_alert(&evt) {
+					continue;
+				}
+
 				match evt {
 					Event::Ais(ais_evt) => match ais_evt {
 						AisEvent::AsstCreated(asst_ref) => {
 							let _ = term.write_line(&format!(
-								"{} Assistant {} created",
+								"{} Assistant {}{} created",
 								ico_check(),
-								asst_ref.name
+								asst_ref.name,
+								id(&asst_ref.id)
 							));
 						}
 						AisEvent::AsstLoaded(asst_ref) => {
 							let _ = term.write_line(&format!(
-								"{} Assistant {} loaded",
+								"{} Assistant {}{} loaded",
 								ico_check(),
-								asst_ref.name
+								asst_ref.name,
+								id(&asst_ref.id)
 							));
 						}
 						AisEvent::AsstDeleted(asst_ref) => {
 							let _ = term.write_line(&format!(
-								"{} Assistant {} deleted",
+								"{} Assistant {}{} deleted",
 								ico_deleted_ok(),
-								asst_ref.name
+								asst_ref.name,
+								id(&asst_ref.id)
 							));
 						}
 						AisEvent::OrgFileDeleted(file_ref) => {
 							let _ = term.write_line(&format!(
-								"{} File {} deleted",
+								"{} File {}{} deleted",
 								ico_deleted_ok(),
-								file_ref.name
+								file_ref.name,
+								id(&file_ref.id)
 							));
 						}
 						AisEvent::OrgFileUploading { file_name } => {
@@ -173,9 +459,10 @@ async fn event_printer(event_bus: &EventBus) -> Result<()> {
 						}
 						AisEvent::OrgFileUploaded(file_ref) => {
 							let _ = term.write_line(&format!(
-								"{} Uploaded  {}",
+								"{} Uploaded  {}{}",
 								ico_uploaded(),
-								file_ref.name
+								file_ref.name,
+								id(&file_ref.id)
 							));
 						}
 
@@ -200,6 +487,23 @@ async fn event_printer(event_bus: &EventBus) -> Result<()> {
 							asst_id
 						));
 						}
+
+						AisEvent::RunFailed(failure) => {
+							let _ = term.write_line(&format!(
+								"{} Run {} failed with {failure}",
+								ico_err(),
+								failure.run_id
+							));
+							for step in failure.steps {
+								let _ = term.write_line(&format!(
+									"   step {} {} {:?} {}",
+									step.step_id,
+									step.kind,
+									step.status,
+									step.message.unwrap_or_default()
+								));
+							}
+						}
 					},
 
 					Event::Buddy(buddy_event) => match buddy_event {
@@ -221,6 +525,109 @@ async fn event_printer(event_bus: &EventBus) -> Result<()> {
 								ico_check()
 							));
 						}
+						BuddyEvent::ConvNoteAdded => {
+							let _ = term.write_line(&format!(
+								"{} Refresh note added to the conversation",
+								ico_check()
+							));
+						}
+						BuddyEvent::BudgetWarning(exceeded) => {
+							let _ = term.write_line(&format!(
+								"{} Budget warning: over the {exceeded}",
+								ico_warn()
+							));
+						}
+						BuddyEvent::BudgetUploadSkipped(exceeded) => {
+							let _ = term.write_line(&format!(
+								"{} Files not uploaded, over the {exceeded} (/rf to confirm)",
+								ico_warn()
+							));
+						}
+						BuddyEvent::BundleFileSkipped {
+							bundle_name,
+							path,
+							reason,
+						} => {
+							let _ = term.write_line(&format!(
+								"{} Skipped in {bundle_name}: {path} ({reason})",
+								ico_warn()
+							));
+						}
+						BuddyEvent::SecretsRedacted {
+							bundle_name,
+							path,
+							count,
+						} => {
+							let _ = term.write_line(&format!(
+								"{} Masked {count} secret(s) in {bundle_name}: {path}",
+								ico_warn()
+							));
+						}
+						BuddyEvent::AttachSecretsRedacted { path, count } => {
+							let _ = term.write_line(&format!(
+								"{} Masked {count} secret(s) in the attached {path}",
+								ico_warn()
+							));
+						}
+						BuddyEvent::BundleSplit { bundle_name, parts } => {
+							let _ = term.write_line(&format!(
+								"{} Bundle {bundle_name} split into {parts} parts",
+								ico_check()
+							));
+						}
+						BuddyEvent::BundlePartRemoved { file_name } => {
+							let _ = term.write_line(&format!(
+								"{} Stale bundle part removed: {file_name}",
+								ico_deleted_ok()
+							));
+						}
+						BuddyEvent::WatchStarted { paths } => {
+							let _ = term.write_line(&format!(
+								"{} Watching {} path(s) for changes",
+								ico_check(),
+								paths.len()
+							));
+							if verbosity == Verbosity::Verbose {
+								for path in paths {
+									let _ = term.write_line(&format!("  {path}"));
+								}
+							}
+						}
+						BuddyEvent::WatchChanged { targets } => {
+							let _ = term.write_line(&format!(
+								"\n{} Changes in {}",
+								ico_uploading(),
+								targets.join(", ")
+							));
+						}
+						BuddyEvent::BundleUpToDate { bundle_name } => {
+							let _ = term.write_line(&format!(
+								"{} Bundle {bundle_name} up to date",
+								ico_check()
+							));
+						}
+						BuddyEvent::WatchUploadFailed { target, cause } => {
+							let _ = term.write_line(&format!(
+								"{} Cannot re-upload {target}: {cause}",
+								ico_err()
+							));
+						}
+						BuddyEvent::FilesAttached { mode, files } => {
+							let mode = match mode {
+								AttachMode::Upload => "uploaded",
+								AttachMode::Inline => "inlined",
+							};
+							let _ = term.write_line(&format!(
+								"{} {} file(s) attached ({mode})",
+								ico_check(),
+								files.len()
+							));
+							if verbosity == Verbosity::Verbose {
+								for file in files {
+									let _ = term.write_line(&format!("  {file}"));
+								}
+							}
+						}
 					},
 				}
 			} else {
@@ -234,3 +641,22 @@ async fn event_printer(event_bus: &EventBus) -> Result<()> {
 
 	Ok(())
 }
+
+/// Returns true for the warning and error events (printed even in quiet mode).
+fn is_alert(evt: &Event) -> bool {
+	matches!(
+		evt,
+		Event::Ais(
+			AisEvent::OrgFileCantDelete { .. }
+				| AisEvent::AsstFileCantRemove { .. }
+				| AisEvent::RunFailed(_)
+		) | Event::Buddy(
+			BuddyEvent::BudgetWarning(_)
+				| BuddyEvent::BudgetUploadSkipped(_)
+				| BuddyEvent::BundleFileSkipped { .. }
+				| BuddyEvent::SecretsRedacted { .. }
+				| BuddyEvent::AttachSecretsRedacted { .. }
+				| BuddyEvent::WatchUploadFailed { .. }
+		)
+	)
+}
@@ -0,0 +1,39 @@
+//! The `buddy plan` mode, a dry run of the startup.
+//!
+//! Prints, like a Terraform plan, what the startup (or `/r`) would create, update, and delete,
+//! without any change. The events (e.g., skipped files) go to stderr.
+
+use crate::args::{Args, OutputMode, Verbosity};
+use crate::utils::cli::txt_plan;
+use crate::{event_printer, Result};
+use ai_buddy::event::EventBus;
+use ai_buddy::Buddy;
+use console::Term;
+
+pub async fn plan(args: &Args) -> Result<()> {
+	let event_bus = EventBus::new();
+	let _ = event_printer(&event_bus, args.verbosity(), args.output, Term::stderr())
+		.await;
+
+	// Note: With `--recreate-asst`, the plan is the one of `/r`, which also recreates
+	//       the conversation.
+	let recreate_conv = args.recreate_conv || args.recreate_asst;
+	let plan = Buddy::plan(
+		&args.dir,
+		args.init_options(),
+		args.conv.as_deref(),
+		recreate_conv,
+		Some(event_bus),
+	)
+	.await?;
+
+	match args.output {
+		OutputMode::Text => {
+			let verbose = args.verbosity() == Verbosity::Verbose;
+			println!("{}", txt_plan(&plan, verbose))
+		}
+		OutputMode::Json => println!("{}", serde_json::to_string(&plan)?),
+	}
+
+	Ok(())
+}
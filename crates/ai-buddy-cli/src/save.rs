@@ -0,0 +1,52 @@
+//! The `/save <n> <path>` command, writing a code block of the last response to a file.
+
+use crate::args::OutputMode;
+use crate::utils::cli::{confirm, print_content, print_msg, txt_diff, MsgLevel};
+use crate::{Error, Result};
+use ai_buddy::ChatResponse;
+use std::fs;
+use std::path::Path;
+
+/// Saves the code block `num` (1-based) of `res` to `path`.
+///
+/// When the file exists, the diff is shown and the user must confirm.
+pub fn save_code_block(
+	res: Option<&ChatResponse>,
+	num: usize,
+	path: &str,
+	output: OutputMode,
+) -> Result<()> {
+	let res = res.ok_or(Error::NoResponseYet)?;
+	let blocks = res.code_blocks();
+	let block = num.checked_sub(1).and_then(|idx| blocks.get(idx)).ok_or(
+		Error::CodeBlockNotFound {
+			num,
+			count: blocks.len(),
+		},
+	)?;
+
+	let file = Path::new(path);
+	if file.is_file() {
+		let current = fs::read_to_string(file)?;
+		let diff = txt_diff(&current, &block.content, path, path);
+		if diff.is_empty() {
+			let msg = format!("{path} is already up to date");
+			print_msg(output, MsgLevel::Success, msg);
+			return Ok(());
+		}
+		print_content(output, diff);
+		if !confirm(output, &format!("Overwrite {path}?"))? {
+			print_msg(output, MsgLevel::Warn, "Not saved");
+			return Ok(());
+		}
+	} else if let Some(parent) = file.parent().filter(|p| !p.as_os_str().is_empty())
+	{
+		fs::create_dir_all(parent)?;
+	}
+
+	fs::write(file, &block.content)?;
+	let msg = format!("Code block [{num}] saved to {path}");
+	print_msg(output, MsgLevel::Success, msg);
+
+	Ok(())
+}
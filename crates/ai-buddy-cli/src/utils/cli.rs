@@ -1,22 +1,83 @@
+use crate::args::OutputMode;
+use crate::utils::md::render_md;
 use crate::Result;
-use console::{style, Style, StyledObject, Term};
+use ai_buddy::{ChatResponse, Plan, PlanAction, PlanTarget, Usage, UsageReport};
+use console::{style, StyledObject, Term};
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::Input;
+use dialoguer::{Confirm, Editor};
+use serde_json::json;
+use similar::{ChangeTag, TextDiff};
+use std::fmt::Display;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // region:    --- Prompts
 
-pub fn prompt(text: &str) -> Result<String> {
-	let theme = ColorfulTheme {
-		prompt_style: Style::new().for_stderr().color256(45),
-		prompt_prefix: style("?".to_string()).color256(45).for_stderr(),
-		..ColorfulTheme::default()
-	};
+/// Reads the lines of a typed or pasted message, until a line with only `.` (or the end of input).
+///
+/// Returns `None` when the message is empty.
+pub fn prompt_multiline(output: OutputMode) -> Result<Option<String>> {
+	// Note: In JSON mode, the instruction is a message on stderr (stdout only has the answers).
+	print_msg(
+		output,
+		MsgLevel::Info,
+		style("Type or paste the message, end with a line containing only `.`")
+			.dim(),
+	);
 
-	Term::stdout().write_line("")?;
-	let input = Input::with_theme(&theme);
-	let res = input.with_prompt(text).interact_text()?;
+	let mut lines = Vec::new();
+	for line in io::stdin().lock().lines() {
+		let line = line?;
+		if line.trim_end() == "." {
+			break;
+		}
+		lines.push(line);
+	}
 
-	Ok(res)
+	Ok(non_empty_msg(lines.join("\n")))
+}
+
+/// Opens `$EDITOR` (or `$VISUAL`) on a temp file, pre-filled with `content`,
+/// and returns the saved message.
+///
+/// Returns `None` when the file was not saved, or is empty.
+pub fn prompt_editor(content: &str) -> Result<Option<String>> {
+	let msg = Editor::new().extension(".md").edit(content)?;
+	Ok(msg.and_then(non_empty_msg))
+}
+
+fn non_empty_msg(msg: String) -> Option<String> {
+	let msg = msg.trim_end();
+	(!msg.trim().is_empty()).then(|| msg.to_string())
+}
+
+/// Asks a yes/no question (no by default).
+///
+/// In JSON mode, the question is a `{"kind": "confirm", "data": {"text"}}` JSON line
+/// on stderr, and the answer is the next stdin line (`y` or `yes` for yes).
+pub fn confirm(output: OutputMode, text: &str) -> Result<bool> {
+	match output {
+		OutputMode::Text => {
+			let theme = ColorfulTheme::default();
+			let res = Confirm::with_theme(&theme)
+				.with_prompt(text)
+				.default(false)
+				.interact()?;
+			Ok(res)
+		}
+		OutputMode::Json => {
+			let line = json!({
+				"kind": "confirm",
+				"data": { "text": text },
+			});
+			eprintln!("{line}");
+
+			let mut answer = String::new();
+			io::stdin().lock().read_line(&mut answer)?;
+			let answer = answer.trim().to_lowercase();
+			Ok(answer == "y" || answer == "yes")
+		}
+	}
 }
 
 // endregion: --- Prompts
@@ -43,16 +104,229 @@ pub fn ico_deleted_ok() -> StyledObject<&'static str> {
 	style("⌫").green()
 }
 
+pub fn ico_warn() -> StyledObject<&'static str> {
+	style("⚠").yellow()
+}
+
 pub fn ico_err() -> StyledObject<&'static str> {
 	style("✗").red()
 }
 
 // endregion: --- Icons
 
+// region:    --- Messages
+
+/// The level of a message (all the output but the answers and the events).
+#[derive(Debug, Clone, Copy)]
+pub enum MsgLevel {
+	Info,
+	Success,
+	Warn,
+	Error,
+}
+
+/// Whether the info and success messages are skipped (`--quiet`).
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Sets the `--quiet` mode of `print_msg` (once, at startup).
+pub fn set_quiet(quiet: bool) {
+	QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Prints a message, on stdout in text mode (with the level icon), or as a
+/// `{"kind": "message", "data": {"level", "text"}}` JSON line on stderr in JSON mode,
+/// like the events.
+///
+/// In quiet mode, only the warnings and errors are printed.
+pub fn print_msg(output: OutputMode, level: MsgLevel, text: impl Display) {
+	if QUIET.load(Ordering::Relaxed)
+		&& matches!(level, MsgLevel::Info | MsgLevel::Success)
+	{
+		return;
+	}
+	print_msg_always(output, level, text);
+}
+
+/// Prints the content asked by the user (e.g., the `/help` text, the diffs to
+/// confirm), even in quiet mode.
+pub fn print_content(output: OutputMode, text: impl Display) {
+	print_msg_always(output, MsgLevel::Info, text);
+}
+
+fn print_msg_always(output: OutputMode, level: MsgLevel, text: impl Display) {
+	match output {
+		OutputMode::Text => {
+			let ico = match level {
+				MsgLevel::Info => None,
+				MsgLevel::Success => Some(ico_check()),
+				MsgLevel::Warn => Some(ico_warn()),
+				MsgLevel::Error => Some(ico_err()),
+			};
+			match ico {
+				Some(ico) => println!("{ico} {text}"),
+				None => println!("{text}"),
+			}
+		}
+		OutputMode::Json => {
+			let level = match level {
+				MsgLevel::Info => "info",
+				MsgLevel::Success => "success",
+				MsgLevel::Warn => "warn",
+				MsgLevel::Error => "error",
+			};
+			let text = console::strip_ansi_codes(&text.to_string()).to_string();
+			let line = json!({
+				"kind": "message",
+				"data": { "level": level, "text": text },
+			});
+			eprintln!("{line}");
+		}
+	}
+}
+
+// endregion: --- Messages
+
 // region:    --- Text Output
 
-pub fn txt_res(text: String) -> StyledObject<String> {
-	style(text).bright()
+/// The response text rendered as Markdown, wrapped to the terminal width.
+pub fn txt_res(text: &str) -> String {
+	let width = Term::stdout()
+		.size_checked()
+		.map(|(_, cols)| cols as usize)
+		.unwrap_or(80);
+	render_md(text, width)
+}
+
+/// The colored unified diff from `old` to `new` (empty when identical).
+pub fn txt_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+	let diff = TextDiff::from_lines(old, new);
+	let mut lines = Vec::new();
+	if diff.ratio() == 1.0 {
+		return String::new();
+	}
+
+	lines.push(style(format!("--- {old_name}")).red().to_string());
+	lines.push(style(format!("+++ {new_name}")).green().to_string());
+	for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+		lines.push(style(hunk.header()).cyan().to_string());
+		for change in hunk.iter_changes() {
+			let line = change.to_string_lossy();
+			let line = line.trim_end_matches(['\r', '\n']);
+			let line = match change.tag() {
+				ChangeTag::Delete => style(format!("-{line}")).red().to_string(),
+				ChangeTag::Insert => style(format!("+{line}")).green().to_string(),
+				ChangeTag::Equal => format!(" {line}"),
+			};
+			lines.push(line);
+		}
+	}
+
+	lines.join("\n")
+}
+
+/// The dimmed line under a response (model, estimated tokens and cost, time).
+pub fn txt_res_meta(res: &ChatResponse) -> StyledObject<String> {
+	let model = res.model.as_deref().unwrap_or("unknown model");
+	let mut text = format!(
+		"  {model} · ~{} tokens · ${:.4} · {:.1}s",
+		res.usage.total_tokens(),
+		res.usage.cost_usd,
+		res.elapsed_ms as f64 / 1000.
+	);
+	let code_blocks = res.code_blocks().len();
+	if code_blocks > 0 {
+		text.push_str(&format!(" · {code_blocks} code block(s), see /save"));
+	}
+	style(text).dim()
+}
+
+pub fn txt_usage_report(report: &UsageReport) -> String {
+	fn line(label: &str, usage: &Usage) -> String {
+		format!(
+			"  {label:<14} {:>4} req  {:>9} in  {:>9} out  ${:.4}",
+			usage.requests,
+			usage.prompt_tokens,
+			usage.completion_tokens,
+			usage.cost_usd
+		)
+	}
+
+	let mut lines = vec![format!(
+		"{} Usage for model {} (estimated tokens)",
+		ico_check(),
+		report.model
+	)];
+	lines.push(line("conversation", &report.conv));
+	lines.push(line("uploads", &report.uploads));
+	lines.push(line("today", &report.today));
+	lines.push(line("total", &report.total));
+	if !report.priced {
+		lines.push(format!(
+			"  {} No price for {} in buddy.toml [prices], cost is $0",
+			ico_err(),
+			report.model
+		));
+	}
+
+	lines.join("\n")
+}
+
+/// The plan, like a Terraform plan (the unchanged items only with `verbose`).
+pub fn txt_plan(plan: &Plan, verbose: bool) -> String {
+	let mut lines = vec![format!(
+		"Buddy {} plan (nothing has been changed)",
+		style(&plan.buddy_name).bold()
+	)];
+	lines.push(String::new());
+
+	for change in plan.changes.iter() {
+		let sign = match change.action {
+			PlanAction::Create => style("  +").green(),
+			PlanAction::Update => style("  ~").yellow(),
+			PlanAction::Replace => style("-/+").yellow(),
+			PlanAction::Delete => style("  -").red(),
+			PlanAction::Keep if verbose => style("  =").dim(),
+			PlanAction::Keep => continue,
+		};
+		let target = match change.target {
+			PlanTarget::Assistant => "assistant",
+			PlanTarget::Instructions => "instructions",
+			PlanTarget::Conversation => "conversation",
+			PlanTarget::RemoteFile => "remote file",
+			PlanTarget::LocalFile => "local file",
+		};
+		let mut line = format!("{sign} {target} {}", change.name);
+		if let Some(detail) = change.detail.as_ref() {
+			line.push_str(&style(format!(" ({detail})")).dim().to_string());
+		}
+		lines.push(line);
+	}
+
+	if !plan.blocked_by_secrets.is_empty() {
+		lines.push(String::new());
+		lines.push(format!(
+			"{} The upload would be blocked, secrets found in (see [redact] policy):",
+			ico_err()
+		));
+		for file in plan.blocked_by_secrets.iter() {
+			lines.push(format!("  {file}"));
+		}
+	}
+
+	lines.push(String::new());
+	if plan.has_changes() {
+		let (to_add, to_change, to_destroy) = plan.counts();
+		lines.push(format!(
+			"Plan: {to_add} to add, {to_change} to change, {to_destroy} to destroy."
+		));
+	} else {
+		lines.push(format!(
+			"{} No changes, the buddy is up to date.",
+			ico_check()
+		));
+	}
+
+	lines.join("\n")
 }
 
 // endregion: --- Text Output
@@ -0,0 +1,148 @@
+//! The REPL line editor, with a persistent history, reverse search (Ctrl-R),
+//! and the tab completion of the slash commands and file paths.
+
+use crate::cmd::CMD_DEFS;
+use crate::Result;
+use console::{colors_enabled, style};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Behavior, CompletionType, Config, Context, Editor, Helper};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+const MAX_HISTORY_SIZE: usize = 1000;
+
+pub struct LineEditor {
+	editor: Editor<LineHelper, DefaultHistory>,
+	history_file: PathBuf,
+}
+
+impl LineEditor {
+	/// Creates the line editor, with the history loaded from `history_file` (if present).
+	///
+	/// With `prefer_term`, the prompt is written to the terminal even when stdout is
+	/// redirected (e.g., to keep stdout for the JSON answers).
+	pub fn new(history_file: impl Into<PathBuf>, prefer_term: bool) -> Result<Self> {
+		let history_file = history_file.into();
+
+		let behavior = if prefer_term {
+			Behavior::PreferTerm
+		} else {
+			Behavior::Stdio
+		};
+		let config = Config::builder()
+			.max_history_size(MAX_HISTORY_SIZE)?
+			.history_ignore_dups(true)?
+			.history_ignore_space(true)
+			.completion_type(CompletionType::List)
+			.behavior(behavior)
+			.build();
+
+		let mut editor = Editor::with_config(config)?;
+		editor.set_helper(Some(LineHelper {
+			file_completer: FilenameCompleter::new(),
+		}));
+		if history_file.exists() {
+			editor.load_history(&history_file)?;
+		}
+
+		Ok(Self {
+			editor,
+			history_file,
+		})
+	}
+
+	/// Reads one non-empty line, and appends it to the history file.
+	///
+	/// Returns `None` on end of input (Ctrl-D). Ctrl-C clears the current line.
+	pub fn read_line(&mut self, text: &str) -> Result<Option<String>> {
+		let prompt = format!("\n? {text} › ");
+		loop {
+			match self.editor.readline(&prompt) {
+				Ok(line) if line.trim().is_empty() => continue,
+				Ok(line) => {
+					if self.editor.add_history_entry(line.as_str())? {
+						self.editor.append_history(&self.history_file)?;
+					}
+					return Ok(Some(line));
+				}
+				Err(ReadlineError::Interrupted) => continue,
+				Err(ReadlineError::Eof) => return Ok(None),
+				Err(err) => return Err(err.into()),
+			}
+		}
+	}
+}
+
+// region:    --- LineHelper
+
+struct LineHelper {
+	file_completer: FilenameCompleter,
+}
+
+impl Completer for LineHelper {
+	type Candidate = Pair;
+
+	/// Completes the command name after a leading `/`, and the file paths in the command arguments.
+	fn complete(
+		&self,
+		line: &str,
+		pos: usize,
+		ctx: &Context<'_>,
+	) -> rustyline::Result<(usize, Vec<Pair>)> {
+		let Some(cmd_line) = line[..pos].strip_prefix('/') else {
+			return Ok((pos, Vec::new()));
+		};
+
+		if cmd_line.contains(char::is_whitespace) {
+			return self.file_completer.complete(line, pos, ctx);
+		}
+
+		let mut names: Vec<&str> = CMD_DEFS
+			.iter()
+			.flat_map(|def| {
+				std::iter::once(def.name).chain(def.aliases.iter().copied())
+			})
+			.filter(|name| name.starts_with(cmd_line))
+			.collect();
+		names.sort_unstable();
+
+		let candidates = names
+			.into_iter()
+			.map(|name| Pair {
+				display: format!("/{name}"),
+				replacement: format!("/{name} "),
+			})
+			.collect();
+
+		Ok((0, candidates))
+	}
+}
+
+impl Highlighter for LineHelper {
+	fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+		&'s self,
+		prompt: &'p str,
+		_default: bool,
+	) -> Cow<'b, str> {
+		if colors_enabled() {
+			Cow::Owned(style(prompt).color256(45).to_string())
+		} else {
+			Cow::Borrowed(prompt)
+		}
+	}
+}
+
+impl Hinter for LineHelper {
+	type Hint = String;
+}
+
+impl Validator for LineHelper {}
+
+impl Helper for LineHelper {}
+
+// endregion: --- LineHelper
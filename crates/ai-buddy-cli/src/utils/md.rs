@@ -0,0 +1,446 @@
+//! Terminal rendering of the Markdown responses.
+//!
+//! The prose (paragraphs, headings, lists, quotes) is wrapped to the given width,
+//! and the fenced code blocks are syntax highlighted and never wrapped, so that
+//! they can be copied as is.
+//!
+//! The fenced code blocks are numbered (e.g., ` ```rust [1] `) for the `/save <n>` and
+//! `/apply <n>` commands. The text is parsed with `ai_buddy::code::md_parser`, the parser of
+//! `ai_buddy::code::extract_code_blocks`, so that the numbers match.
+
+use ai_buddy::code::md_parser;
+use console::{colors_enabled, style, Style};
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+use textwrap::wrap;
+
+const CODE_THEME: &str = "base16-ocean.dark";
+
+/// Renders the Markdown `text` for the terminal, wrapping the prose at `width`.
+pub fn render_md(text: &str, width: usize) -> String {
+	let mut renderer = MdRenderer::new(width);
+	for event in md_parser(text) {
+		renderer.on_event(event);
+	}
+	renderer.finish()
+}
+
+// region:    --- MdRenderer
+
+struct MdRenderer {
+	width: usize,
+	out: Vec<String>,
+	/// The styled inline text of the current block.
+	text: String,
+	lists: Vec<ListState>,
+	/// The bullet or number of the current item, for its first line.
+	item_marker: Option<String>,
+	quote_depth: usize,
+	heading: bool,
+	strong: usize,
+	emphasis: usize,
+	strikethrough: usize,
+	links: Vec<String>,
+	/// The code block being read.
+	code: Option<CodeState>,
+	/// Number of fenced code blocks so far.
+	code_count: usize,
+}
+
+impl MdRenderer {
+	fn new(width: usize) -> Self {
+		Self {
+			width,
+			out: Vec::new(),
+			text: String::new(),
+			lists: Vec::new(),
+			item_marker: None,
+			quote_depth: 0,
+			heading: false,
+			strong: 0,
+			emphasis: 0,
+			strikethrough: 0,
+			links: Vec::new(),
+			code: None,
+			code_count: 0,
+		}
+	}
+
+	fn on_event(&mut self, event: Event) {
+		match event {
+			// -- Blocks
+			Event::Start(Tag::Paragraph) => self.flush(),
+			Event::End(TagEnd::Paragraph) => {
+				self.flush();
+				self.blank_line_unless_in_list();
+			}
+			Event::Start(Tag::Heading { .. }) => {
+				self.flush();
+				self.heading = true;
+			}
+			Event::End(TagEnd::Heading(_)) => {
+				self.flush();
+				self.heading = false;
+				self.blank_line();
+			}
+			Event::Start(Tag::BlockQuote(_)) => {
+				self.flush();
+				self.quote_depth += 1;
+			}
+			Event::End(TagEnd::BlockQuote(_)) => {
+				self.flush();
+				self.quote_depth -= 1;
+				self.blank_line_unless_in_list();
+			}
+			Event::Start(Tag::List(start)) => {
+				self.flush();
+				self.lists.push(ListState {
+					next_num: start,
+					marker_width: 0,
+				});
+			}
+			Event::End(TagEnd::List(_)) => {
+				self.flush();
+				self.lists.pop();
+				self.blank_line_unless_in_list();
+			}
+			Event::Start(Tag::Item) => {
+				self.flush();
+				if let Some(list) = self.lists.last_mut() {
+					let marker = match list.next_num.as_mut() {
+						Some(num) => {
+							*num += 1;
+							format!("{}. ", *num - 1)
+						}
+						None => "• ".to_string(),
+					};
+					list.marker_width = marker.chars().count();
+					self.item_marker = Some(marker);
+				}
+			}
+			Event::End(TagEnd::Item) => self.flush(),
+			Event::Start(Tag::CodeBlock(kind)) => {
+				self.flush();
+				let (lang, num) = match kind {
+					CodeBlockKind::Fenced(info) => {
+						self.code_count += 1;
+						let lang =
+							info.split_whitespace().next().unwrap_or_default();
+						(lang.to_string(), Some(self.code_count))
+					}
+					CodeBlockKind::Indented => (String::new(), None),
+				};
+				self.code = Some(CodeState {
+					lang,
+					num,
+					content: String::new(),
+				});
+			}
+			Event::End(TagEnd::CodeBlock) => {
+				if let Some(code) = self.code.take() {
+					self.push_code_block(code);
+				}
+				self.blank_line_unless_in_list();
+			}
+			Event::Rule => {
+				self.flush();
+				let rule = "─".repeat(self.width.min(40));
+				self.out.push(style(rule).dim().to_string());
+				self.blank_line();
+			}
+
+			// -- Inlines
+			Event::Start(Tag::Strong) => self.strong += 1,
+			Event::End(TagEnd::Strong) => self.strong -= 1,
+			Event::Start(Tag::Emphasis) => self.emphasis += 1,
+			Event::End(TagEnd::Emphasis) => self.emphasis -= 1,
+			Event::Start(Tag::Strikethrough) => self.strikethrough += 1,
+			Event::End(TagEnd::Strikethrough) => self.strikethrough -= 1,
+			Event::Start(Tag::Link { dest_url, .. }) => {
+				self.links.push(dest_url.to_string())
+			}
+			Event::End(TagEnd::Link) => {
+				if let Some(url) = self.links.pop() {
+					if !url.is_empty() && !self.text.ends_with(url.as_str()) {
+						self.text
+							.push_str(&style(format!(" ({url})")).dim().to_string());
+					}
+				}
+			}
+			Event::Text(text) => match self.code.as_mut() {
+				Some(code) => code.content.push_str(&text),
+				None => {
+					let styled = self.inline_style().apply_to(text).to_string();
+					self.text.push_str(&styled);
+				}
+			},
+			Event::Code(code) => {
+				self.text.push_str(&style(code).color256(179).to_string())
+			}
+			Event::Html(html) | Event::InlineHtml(html) => self.text.push_str(&html),
+			Event::TaskListMarker(checked) => {
+				self.text.push_str(if checked { "[x] " } else { "[ ] " })
+			}
+			Event::SoftBreak => self.text.push(' '),
+			Event::HardBreak => self.text.push('\n'),
+
+			// Note: Other events (e.g., footnotes, math) are not enabled in the parser options.
+			_ => (),
+		}
+	}
+
+	fn finish(mut self) -> String {
+		self.flush();
+		while self.out.last().is_some_and(|l| l.is_empty()) {
+			self.out.pop();
+		}
+		self.out.join("\n")
+	}
+
+	// -- Support
+
+	fn inline_style(&self) -> Style {
+		let mut style = Style::new();
+		if self.heading {
+			style = style.bold().color256(45);
+		}
+		if self.strong > 0 {
+			style = style.bold();
+		}
+		if self.emphasis > 0 {
+			style = style.italic();
+		}
+		if self.strikethrough > 0 {
+			style = style.strikethrough();
+		}
+		if !self.links.is_empty() {
+			style = style.underlined();
+		}
+		style
+	}
+
+	/// The `(first_line, other_lines)` prefix of the current block (quotes and list items).
+	fn indents(&mut self) -> (String, String) {
+		let quote = match self.quote_depth {
+			0 => String::new(),
+			depth => style("│ ".repeat(depth)).dim().to_string(),
+		};
+		let Some((current, parents)) = self.lists.split_last() else {
+			return (quote.clone(), quote);
+		};
+
+		// Nested lists, continuation lines, and next blocks of an item
+		// are aligned after the item marker.
+		let parents_width: usize = parents.iter().map(|l| l.marker_width).sum();
+		let parents_pad = " ".repeat(parents_width);
+		let others =
+			format!("{quote}{parents_pad}{}", " ".repeat(current.marker_width));
+		let first = match self.item_marker.take() {
+			Some(marker) => format!("{quote}{parents_pad}{marker}"),
+			None => others.clone(),
+		};
+		(first, others)
+	}
+
+	/// Wraps and writes the pending inline text.
+	fn flush(&mut self) {
+		let text = std::mem::take(&mut self.text);
+		if text.trim().is_empty() {
+			return;
+		}
+		let (first, others) = self.indents();
+		let options = textwrap::Options::new(self.width)
+			.initial_indent(&first)
+			.subsequent_indent(&others);
+		for line in wrap(text.trim(), options) {
+			self.out.push(line.into_owned());
+		}
+	}
+
+	fn push_code_block(&mut self, code: CodeState) {
+		let (first, others) = self.indents();
+		let CodeState { lang, num, content } = code;
+
+		let num = num.map(|n| format!(" [{n}]")).unwrap_or_default();
+		let open = style(format!("```{lang}{num}")).dim();
+		self.out.push(format!("{first}{open}"));
+		for line in highlight(&lang, &content) {
+			self.out.push(format!("{others}{line}"));
+		}
+		self.out.push(format!("{others}{}", style("```").dim()));
+	}
+
+	fn blank_line(&mut self) {
+		if self.out.last().is_some_and(|l| !l.is_empty()) {
+			self.out.push(String::new());
+		}
+	}
+
+	fn blank_line_unless_in_list(&mut self) {
+		if self.lists.is_empty() {
+			self.blank_line();
+		}
+	}
+}
+
+struct CodeState {
+	lang: String,
+	/// The block number, for the fenced code blocks.
+	num: Option<usize>,
+	content: String,
+}
+
+struct ListState {
+	/// `None` for bullet lists, the next item number for ordered lists.
+	next_num: Option<u64>,
+	/// Display width of the current item marker (e.g., `• ` or `10. `).
+	marker_width: usize,
+}
+
+// endregion: --- MdRenderer
+
+// region:    --- Code Highlight
+
+fn syntax_set() -> &'static SyntaxSet {
+	static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+	SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+	static THEME: OnceLock<Theme> = OnceLock::new();
+	THEME.get_or_init(|| {
+		let mut themes = ThemeSet::load_defaults().themes;
+		themes.remove(CODE_THEME).unwrap_or_default()
+	})
+}
+
+/// Highlights the code lines with the syntax of `lang` (plain text when colors are off,
+/// or when the language is unknown).
+fn highlight(lang: &str, code: &str) -> Vec<String> {
+	let syntax_set = syntax_set();
+	let syntax = (!lang.is_empty())
+		.then(|| syntax_set.find_syntax_by_token(lang))
+		.flatten();
+	let Some(syntax) = syntax.filter(|_| colors_enabled()) else {
+		return code.lines().map(String::from).collect();
+	};
+
+	let mut highlighter = HighlightLines::new(syntax, theme());
+	LinesWithEndings::from(code)
+		.map(|line| match highlighter.highlight_line(line, syntax_set) {
+			Ok(ranges) => {
+				let escaped = as_24_bit_terminal_escaped(&ranges, false);
+				// Reset the colors at the end of each line.
+				format!("{}\x1b[0m", escaped.trim_end_matches(['\r', '\n']))
+			}
+			Err(_) => line.trim_end_matches(['\r', '\n']).to_string(),
+		})
+		.collect()
+}
+
+// endregion: --- Code Highlight
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use ai_buddy::code::extract_code_blocks;
+
+	/// Renders without colors (the checks are on the plain text).
+	fn render_plain(text: &str, width: usize) -> String {
+		console::set_colors_enabled(false);
+		render_md(text, width)
+	}
+
+	#[test]
+	fn test_render_md_wrap_width_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "This paragraph is long enough to be wrapped on a few lines.\n\n\
+			```rust\nlet a_long_line_of_code_that_is_never_wrapped = 1;\n```";
+
+		// -- Exec
+		let res = render_plain(text, 20);
+
+		// -- Check
+		let lines: Vec<&str> = res.lines().collect();
+		assert_eq!(
+			&lines[..4],
+			[
+				"This paragraph is",
+				"long enough to be",
+				"wrapped on a few",
+				"lines."
+			]
+		);
+		assert!(
+			lines.contains(&"let a_long_line_of_code_that_is_never_wrapped = 1;")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_render_md_list_indent_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "- first item\n- second item, long enough to wrap\n  1. nested one\n  2. nested two\n- last";
+
+		// -- Exec
+		let res = render_plain(text, 24);
+
+		// -- Check
+		assert_eq!(
+			res,
+			"• first item\n\
+			 • second item, long\n  \
+			   enough to wrap\n  \
+			   1. nested one\n  \
+			   2. nested two\n\
+			 • last"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_render_md_code_block_nums_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// Note: Fences in list items and quotes count, indented code blocks do not.
+		let text = "Intro\n\n```rust\nfn a() {}\n```\n\n\
+			- item\n\n  ```toml\n  a = 1\n  ```\n\n\
+			> quote\n>\n> ```sh\n> ls\n> ```\n\n    \
+			indented code\n\n\
+			```\nplain\n```\n";
+
+		// -- Exec
+		let res = render_plain(text, 80);
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		let fences: Vec<String> = res
+			.lines()
+			.map(|l| l.trim_start_matches(['│', ' ']))
+			.filter(|l| l.starts_with("```") && l.len() > 3)
+			.map(String::from)
+			.collect();
+		assert_eq!(
+			fences,
+			["```rust [1]", "```toml [2]", "```sh [3]", "``` [4]"]
+		);
+		assert_eq!(blocks.len(), 4);
+		let langs: Vec<Option<&str>> =
+			blocks.iter().map(|b| b.lang.as_deref()).collect();
+		assert_eq!(langs, [Some("rust"), Some("toml"), Some("sh"), None]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
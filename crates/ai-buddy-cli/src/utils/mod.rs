@@ -1,5 +1,7 @@
 // region:    --- Modules
 
 pub mod cli;
+pub mod line_editor;
+pub mod md;
 
 // endregion: --- Modules
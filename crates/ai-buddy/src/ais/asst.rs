@@ -1,15 +1,19 @@
-use crate::ais::msg::{get_text_content, user_msg};
-use crate::ais::{AisClient, AisEvent, AsstId, AsstRef, FileId, FileRef, ThreadId};
+use crate::ais::msg::{get_chat_response, user_msg, user_msg_with_files};
+use crate::ais::{
+	AisClient, AisEvent, AsstId, AsstRef, ChatResponse, FileId, FileRef, RunFailure,
+	RunStepInfo, ThreadId,
+};
 use crate::{Error, Result};
 use async_openai::types::{
 	AssistantObject, AssistantToolsRetrieval, CreateAssistantFileRequest,
 	CreateAssistantRequest, CreateFileRequest, CreateRunRequest,
-	CreateThreadRequest, ModifyAssistantRequest, RunStatus, ThreadObject,
+	CreateThreadRequest, LastError, LastErrorCode, ModifyAssistantRequest,
+	RunObject, RunStatus, RunStepType, ThreadObject,
 };
 use console::Term;
 use simple_fs::SPath;
 use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 // region:    --- Constants
@@ -183,15 +187,40 @@ pub async fn get_thread(
 	Ok(thread_obj)
 }
 
+/// Adds a user message to the thread, without running the assistant
+/// (e.g., a note taken into account by the next run).
+pub async fn add_thread_msg(
+	ais: &AisClient,
+	thread_id: &ThreadId,
+	msg: &str,
+) -> Result<()> {
+	let oac = ais.oa_client();
+
+	oac.threads()
+		.messages(thread_id)
+		.create(user_msg(msg))
+		.await?;
+
+	Ok(())
+}
+
 pub async fn run_thread_msg(
 	ais: &AisClient,
 	asst_id: &AsstId,
 	thread_id: &ThreadId,
 	msg: &str,
-) -> Result<String> {
+	file_ids: &[FileId],
+	// Overrides the assistant model for this run only.
+	model: Option<&str>,
+) -> Result<ChatResponse> {
 	let oac = ais.oa_client();
+	let start = Instant::now();
 
-	let msg = user_msg(msg);
+	let msg = if file_ids.is_empty() {
+		user_msg(msg)
+	} else {
+		user_msg_with_files(msg, file_ids)
+	};
 
 	// -- Attach message to thread
 	let _message_obj = oac.threads().messages(thread_id).create(msg).await?;
@@ -199,25 +228,39 @@ pub async fn run_thread_msg(
 	// -- Create a run for the thread
 	let run_request = CreateRunRequest {
 		assistant_id: asst_id.to_string(),
+		model: model.map(String::from),
 		..Default::default()
 	};
 	let run = oac.threads().runs(thread_id).create(run_request).await?;
 
 	// -- Loop to get result
-	let term = Term::stdout();
+	// Note: The progress goes to stderr, so that stdout only has the answers.
+	let progress = |text: &str| {
+		if ais.run_progress() {
+			Term::stderr().write_str(text)
+		} else {
+			Ok(())
+		}
+	};
 	loop {
-		term.write_str("›")?;
+		progress("›")?;
 		let run = oac.threads().runs(thread_id).retrieve(&run.id).await?;
-		term.write_str("‹ ")?;
+		progress("‹ ")?;
 		match run.status {
 			RunStatus::Completed => {
-				term.write_str("\n")?;
-				return get_first_thread_msg_content(ais, thread_id).await;
+				progress("\n")?;
+				let mut res = get_first_thread_msg_content(ais, thread_id).await?;
+				res.run_id.get_or_insert(run.id);
+				res.model = Some(run.model);
+				res.elapsed_ms = start.elapsed().as_millis() as u64;
+				return Ok(res);
 			}
 			RunStatus::Queued | RunStatus::InProgress => (),
-			other => {
-				term.write_str("\n")?;
-				return Err(Error::RunError(other));
+			_ => {
+				progress("\n")?;
+				let failure = get_run_failure(ais, thread_id, run).await;
+				ais.event_bus().send(AisEvent::RunFailed(failure.clone()))?;
+				return Err(Error::RunError(failure));
 			}
 		}
 
@@ -225,10 +268,71 @@ pub async fn run_thread_msg(
 	}
 }
 
+/// Builds the `RunFailure` of a run that did not complete, with its last error and steps.
+///
+/// Note: The steps are informative, so failing to fetch them is not an error.
+async fn get_run_failure(
+	ais: &AisClient,
+	thread_id: &ThreadId,
+	run: RunObject,
+) -> RunFailure {
+	let (code, message) = split_last_error(run.last_error);
+
+	let steps = ais
+		.oa_client()
+		.threads()
+		.runs(thread_id)
+		.steps(&run.id)
+		.list(DEFAULT_QUERY)
+		.await
+		.map(|steps| steps.data)
+		.unwrap_or_default()
+		.into_iter()
+		.map(|step| {
+			let (code, message) = split_last_error(step.last_error);
+			RunStepInfo {
+				step_id: step.id,
+				kind: match step.r#type {
+					RunStepType::MessageCreation => "message_creation",
+					RunStepType::ToolCalls => "tool_calls",
+				}
+				.to_string(),
+				status: step.status,
+				code,
+				message,
+			}
+		})
+		.collect();
+
+	RunFailure {
+		run_id: run.id,
+		status: run.status,
+		code,
+		message,
+		steps,
+	}
+}
+
+/// Returns the `(code, message)` of a run or step `last_error`.
+fn split_last_error(
+	last_error: Option<LastError>,
+) -> (Option<String>, Option<String>) {
+	match last_error {
+		Some(last_error) => {
+			let code = match last_error.code {
+				LastErrorCode::ServerError => "server_error",
+				LastErrorCode::RateLimitExceeded => "rate_limit_exceeded",
+			};
+			(Some(code.to_string()), Some(last_error.message))
+		}
+		None => (None, None),
+	}
+}
+
 pub async fn get_first_thread_msg_content(
 	ais: &AisClient,
 	thread_id: &ThreadId,
-) -> Result<String> {
+) -> Result<ChatResponse> {
 	let oac = ais.oa_client();
 
 	static QUERY: [(&str, &str); 1] = [("limit", "1")];
@@ -240,9 +344,9 @@ pub async fn get_first_thread_msg_content(
 		.next()
 		.ok_or(Error::NoMessageFoundInMessages)?;
 
-	let text = get_text_content(msg)?;
+	let res = get_chat_response(msg)?;
 
-	Ok(text)
+	Ok(res)
 }
 
 // endregion: --- Thread
@@ -304,25 +408,7 @@ pub async fn upload_file_by_name(
 
 	// -- If we have old file_id, we delete the file.
 	if let Some(file_id) = file_id {
-		// -- Delete the org file
-		let oa_files = oac.files();
-		if let Err(err) = oa_files.delete(&file_id).await {
-			ais.event_bus().send(AisEvent::OrgFileCantDelete {
-				file_ref: FileRef::new(file, file_id.clone()),
-				cause: err.to_string(),
-			})?;
-		}
-
-		// -- Delete the asst_file association
-		let oa_assts = oac.assistants();
-		let oa_assts_files = oa_assts.files(asst_id);
-		if let Err(err) = oa_assts_files.delete(&file_id).await {
-			ais.event_bus().send(AisEvent::AsstFileCantRemove {
-				asst_id: asst_id.clone(),
-				file_id: file_id.clone(),
-				cause: err.to_string(),
-			})?;
-		}
+		delete_asst_file(ais, asst_id, FileRef::new(file, file_id)).await?;
 	}
 
 	// -- Upload and attach the file.
@@ -366,4 +452,78 @@ pub async fn upload_file_by_name(
 	Ok((asst_file_obj.id.into(), true))
 }
 
+/// Deletes an assistant file (the account file, and its assistant association).
+///
+/// Note: The failures are only reported as events (e.g., might be already deleted).
+pub async fn delete_asst_file(
+	ais: &AisClient,
+	asst_id: &AsstId,
+	file_ref: FileRef,
+) -> Result<()> {
+	let oac = ais.oa_client();
+	let file_id = file_ref.id.clone();
+
+	// -- Delete the org file
+	let oa_files = oac.files();
+	if let Err(err) = oa_files.delete(&file_id).await {
+		ais.event_bus().send(AisEvent::OrgFileCantDelete {
+			file_ref,
+			cause: err.to_string(),
+		})?;
+	}
+
+	// -- Delete the asst_file association
+	let oa_assts = oac.assistants();
+	let oa_assts_files = oa_assts.files(asst_id);
+	if let Err(err) = oa_assts_files.delete(&file_id).await {
+		ais.event_bus().send(AisEvent::AsstFileCantRemove {
+			asst_id: asst_id.clone(),
+			file_id,
+			cause: err.to_string(),
+		})?;
+	}
+
+	Ok(())
+}
+
+/// Uploads a file to the account only (e.g., to attach it to a single message).
+pub async fn upload_org_file(ais: &AisClient, file: &SPath) -> Result<FileId> {
+	ais.event_bus().send(AisEvent::OrgFileUploading {
+		file_name: file.file_name().to_string(),
+	})?;
+
+	let oa_file = ais
+		.oa_client()
+		.files()
+		.create(CreateFileRequest {
+			file: file.into(),
+			purpose: "assistants".into(),
+		})
+		.await?;
+	let file_id: FileId = oa_file.id.into();
+
+	ais.event_bus()
+		.send(AisEvent::OrgFileUploaded(FileRef::new(
+			file,
+			file_id.clone(),
+		)))?;
+
+	Ok(file_id)
+}
+
+/// Deletes an account file.
+///
+/// Note: A failure is only reported as an `OrgFileCantDelete` event (e.g., might be already deleted).
+pub async fn delete_org_file(ais: &AisClient, file_ref: FileRef) -> Result<()> {
+	match ais.oa_client().files().delete(&file_ref.id).await {
+		Ok(_) => ais.event_bus().send(AisEvent::OrgFileDeleted(file_ref))?,
+		Err(err) => ais.event_bus().send(AisEvent::OrgFileCantDelete {
+			file_ref,
+			cause: err.to_string(),
+		})?,
+	};
+
+	Ok(())
+}
+
 // endregion: --- Files
@@ -1,8 +1,10 @@
 //! Ais Event
 
-use crate::ais::{AsstId, AsstRef, FileId, FileRef};
+use crate::ais::{AsstId, AsstRef, FileId, FileRef, RunFailure};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
 pub enum AisEvent {
 	// -- Asst Events
 	AsstCreated(AsstRef),
@@ -25,4 +27,7 @@ pub enum AisEvent {
 		file_ref: FileRef,
 		cause: String,
 	},
+
+	// -- Run Events
+	RunFailed(RunFailure),
 }
@@ -35,15 +35,25 @@ pub type OaClient = Client<OpenAIConfig>;
 pub struct AisClient {
 	oa_client: OaClient,
 	event_bus: EventBus,
+	/// Print the run polling progress (`›‹`) on stderr (off by default).
+	run_progress: bool,
 }
 
 impl AisClient {
+	pub fn with_run_progress(mut self, run_progress: bool) -> Self {
+		self.run_progress = run_progress;
+		self
+	}
+
 	pub fn oa_client(&self) -> &OaClient {
 		&self.oa_client
 	}
 	pub fn event_bus(&self) -> &EventBus {
 		&self.event_bus
 	}
+	pub fn run_progress(&self) -> bool {
+		self.run_progress
+	}
 }
 
 pub fn new_ais_client(event_bus: EventBus) -> Result<AisClient> {
@@ -51,10 +61,9 @@ pub fn new_ais_client(event_bus: EventBus) -> Result<AisClient> {
 		Ok(AisClient {
 			oa_client: Client::new(),
 			event_bus,
+			run_progress: false,
 		})
 	} else {
-		println!("No {ENV_OPENAI_API_KEY} env variable. Please set it.");
-
 		Err(Error::NoOpenAIApiKeyInEnv)
 	}
 }
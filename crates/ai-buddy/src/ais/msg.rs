@@ -1,5 +1,9 @@
+use crate::ais::{Annotation, AnnotationKind, ChatResponse, FileId, Usage};
 use crate::{Error, Result};
-use async_openai::types::{CreateMessageRequest, MessageContent, MessageObject};
+use async_openai::types::{
+	CreateMessageRequest, MessageContent, MessageContentTextAnnotations,
+	MessageObject,
+};
 
 // region:    --- Message Constructors
 
@@ -11,11 +15,26 @@ pub fn user_msg(content: impl Into<String>) -> CreateMessageRequest {
 	}
 }
 
+/// A user message with uploaded files attached (e.g., for the retrieval tool).
+pub fn user_msg_with_files(
+	content: impl Into<String>,
+	file_ids: &[FileId],
+) -> CreateMessageRequest {
+	let file_ids = file_ids.iter().map(|id| id.to_string()).collect();
+	CreateMessageRequest {
+		file_ids: Some(file_ids),
+		..user_msg(content)
+	}
+}
+
 // endregion: --- Message Constructors
 
 // region:    --- Content Extractor
 
-pub fn get_text_content(msg: MessageObject) -> Result<String> {
+/// Builds the `ChatResponse` from the message text content.
+///
+/// Note: `model`, `elapsed_ms`, and `usage` are not known from the message and left empty.
+pub fn get_chat_response(msg: MessageObject) -> Result<ChatResponse> {
 	// -- Get the first content item
 	let msg_content = msg
 		.content
@@ -24,12 +43,45 @@ pub fn get_text_content(msg: MessageObject) -> Result<String> {
 		.ok_or(Error::NoMessageInMessageObjectContent)?;
 
 	// -- Get the text
-	let txt = match msg_content {
-		MessageContent::Text(text) => text.text.value,
+	let text_data = match msg_content {
+		MessageContent::Text(text) => text.text,
 		MessageContent::ImageFile(_) => return Err(Error::MessageImageNotSupported),
 	};
 
-	Ok(txt)
+	let annotations = text_data
+		.annotations
+		.into_iter()
+		.map(|annotation| match annotation {
+			MessageContentTextAnnotations::FileCitation(citation) => Annotation {
+				kind: AnnotationKind::FileCitation,
+				text: citation.text,
+				file_id: citation.file_citation.file_id,
+				quote: Some(citation.file_citation.quote),
+				start_index: citation.start_index,
+				end_index: citation.end_index,
+			},
+			MessageContentTextAnnotations::FilePath(path) => Annotation {
+				kind: AnnotationKind::FilePath,
+				text: path.text,
+				file_id: path.file_path.file_id,
+				quote: None,
+				start_index: path.start_index,
+				end_index: path.end_index,
+			},
+		})
+		.collect();
+
+	Ok(ChatResponse {
+		text: text_data.value,
+		msg_id: msg.id,
+		thread_id: msg.thread_id,
+		run_id: msg.run_id,
+		model: None,
+		created_at: msg.created_at.into(),
+		elapsed_ms: 0,
+		usage: Usage::default(),
+		annotations,
+	})
 }
 
 // endregion: --- Content Extractor
@@ -1,9 +1,13 @@
+use crate::code::{extract_code_blocks, CodeBlock};
+use async_openai::types::RunStatus;
 use derive_more::{Deref, Display, From};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::AddAssign;
 
 // region:    --- Asst
 
-#[derive(Debug, Clone, From, Deref, Display)]
+#[derive(Debug, Clone, From, Deref, Display, Serialize)]
 pub struct AsstId(String);
 
 impl From<&AsstId> for AsstId {
@@ -12,7 +16,7 @@ impl From<&AsstId> for AsstId {
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AsstRef {
 	pub name: String,
 	pub id: AsstId,
@@ -31,10 +35,10 @@ impl AsstRef {
 
 // region:    --- File
 
-#[derive(Debug, Clone, From, Deref, Display)]
+#[derive(Debug, Clone, From, Deref, Display, Serialize)]
 pub struct FileId(String);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FileRef {
 	pub name: String,
 	pub id: FileId,
@@ -51,6 +55,124 @@ impl FileRef {
 
 // endregion: --- File
 
+// region:    --- Chat
+
+/// The response of a chat message run, with its metadata.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatResponse {
+	pub text: String,
+	pub msg_id: String,
+	pub thread_id: String,
+	pub run_id: Option<String>,
+	/// The model used by the run (when known).
+	pub model: Option<String>,
+	/// Unix timestamp (in seconds) of the response message creation.
+	pub created_at: i64,
+	/// Time from the message submission to the run completion.
+	pub elapsed_ms: u64,
+	pub usage: Usage,
+	pub annotations: Vec<Annotation>,
+}
+
+impl ChatResponse {
+	/// The fenced code blocks of the response text, in order.
+	pub fn code_blocks(&self) -> Vec<CodeBlock> {
+		extract_code_blocks(&self.text)
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+	/// Quote from a file (retrieval tool).
+	FileCitation,
+	/// File generated by the assistant (code interpreter tool).
+	FilePath,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Annotation {
+	pub kind: AnnotationKind,
+	/// The text in the response that the annotation is for.
+	pub text: String,
+	pub file_id: String,
+	pub quote: Option<String>,
+	pub start_index: u32,
+	pub end_index: u32,
+}
+
+/// Tokens and cost of one or more requests.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+	pub requests: u64,
+	pub prompt_tokens: u64,
+	pub completion_tokens: u64,
+	pub cost_usd: f64,
+}
+
+impl Usage {
+	pub fn new(prompt_tokens: u64, completion_tokens: u64, cost_usd: f64) -> Self {
+		Usage {
+			requests: 1,
+			prompt_tokens,
+			completion_tokens,
+			cost_usd,
+		}
+	}
+
+	pub fn total_tokens(&self) -> u64 {
+		self.prompt_tokens + self.completion_tokens
+	}
+}
+
+impl AddAssign<&Usage> for Usage {
+	fn add_assign(&mut self, other: &Usage) {
+		self.requests += other.requests;
+		self.prompt_tokens += other.prompt_tokens;
+		self.completion_tokens += other.completion_tokens;
+		self.cost_usd += other.cost_usd;
+	}
+}
+
+// endregion: --- Chat
+
+// region:    --- Run
+
+/// Details of a run that did not complete (failed, expired, cancelled, ...).
+#[derive(Debug, Clone, Serialize)]
+pub struct RunFailure {
+	pub run_id: String,
+	pub status: RunStatus,
+	/// The run `last_error.code` (e.g., `rate_limit_exceeded`, `server_error`).
+	pub code: Option<String>,
+	pub message: Option<String>,
+	pub steps: Vec<RunStepInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunStepInfo {
+	pub step_id: String,
+	/// `message_creation` or `tool_calls`.
+	pub kind: String,
+	pub status: RunStatus,
+	pub code: Option<String>,
+	pub message: Option<String>,
+}
+
+impl fmt::Display for RunFailure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "status {:?}", self.status)?;
+		match (&self.code, &self.message) {
+			(Some(code), Some(message)) => write!(f, " ({code}: {message})"),
+			(Some(code), None) => write!(f, " ({code})"),
+			(None, Some(message)) => write!(f, " ({message})"),
+			(None, None) => Ok(()),
+		}
+	}
+}
+
+// endregion: --- Run
+
 // region:    --- ThreadId
 
 #[derive(Debug, From, Deref, Display, Serialize, Deserialize)]
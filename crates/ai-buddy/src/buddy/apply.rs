@@ -0,0 +1,237 @@
+//! Resolution of the patch files against the `file_bundles` source directories
+//! (see `Buddy::check_patch` and `Buddy::apply_changes`).
+
+use crate::buddy::bundle::is_excluded;
+use crate::buddy::config::FileBundle;
+use crate::{Error, Result};
+use simple_fs::get_glob_set;
+use std::path::{Component, Path, PathBuf};
+
+/// A file change of a patch, checked against the current file content.
+#[derive(Debug, Clone)]
+pub struct FileChange {
+	/// The local file path.
+	pub path: PathBuf,
+	/// The bundle of the file.
+	pub bundle_name: String,
+	/// The path relative to the bundle `src_dir`.
+	pub rel_path: String,
+	/// `None` for a new file.
+	pub old_content: Option<String>,
+	pub new_content: String,
+}
+
+/// A patch file resolved to a bundle file.
+pub(super) struct ResolvedPath<'a> {
+	pub bundle: &'a FileBundle,
+	pub path: PathBuf,
+	pub rel_path: String,
+}
+
+/// Resolves a patch path to a file of a bundle `src_dir`.
+///
+/// The patch path might be relative to the `src_dir` or to one of its parent
+/// directories (e.g., `crates/ai-buddy/src/lib.rs` for the `../crates` src_dir), so
+/// the leading components can be dropped, but only when they are the last names of
+/// the `src_dir` path.
+///
+/// The file must match the `src_globs` of the bundle (and not its `exclude_globs`).
+/// A new file must not exist, and its parent directory must exist.
+///
+/// When the path matches the files of several bundles, `Error::PatchPathInvalid`
+/// is returned (rather than patching the wrong one).
+pub(super) fn resolve_patch_path<'a>(
+	dir: &Path,
+	bundles: &'a [FileBundle],
+	patch_path: &str,
+	is_new: bool,
+) -> Result<ResolvedPath<'a>> {
+	let components: Vec<&str> = Path::new(patch_path)
+		.components()
+		.map(|c| match c {
+			Component::Normal(name) => Ok(name.to_str().unwrap_or_default()),
+			Component::CurDir => Ok(""),
+			_ => Err(Error::PatchPathInvalid {
+				path: patch_path.to_string(),
+				reason: "must be relative, without '..'".to_string(),
+			}),
+		})
+		.collect::<Result<Vec<_>>>()?
+		.into_iter()
+		.filter(|c| !c.is_empty())
+		.collect();
+
+	let mut found: Vec<ResolvedPath<'a>> = Vec::new();
+	for bundle in bundles {
+		let src_dir = dir.join(&bundle.src_dir);
+		let src_dir_full =
+			src_dir.canonicalize().unwrap_or_else(|_| src_dir.clone());
+		let src_dir_names: Vec<&str> = src_dir_full
+			.components()
+			.filter_map(|c| match c {
+				Component::Normal(name) => name.to_str(),
+				_ => None,
+			})
+			.collect();
+
+		let src_globs = get_glob_set(&as_strs(&bundle.src_globs))?;
+		for skip in 0..components.len() {
+			if !src_dir_names.ends_with(&components[..skip]) {
+				continue;
+			}
+			let rel_path = components[skip..].join("/");
+			let path = src_dir.join(&rel_path);
+
+			let in_bundle =
+				src_globs.is_match(&rel_path) && !is_excluded(bundle, &rel_path)?;
+			let exists = if is_new {
+				let parent_exists = path.parent().is_some_and(|p| p.is_dir());
+				parent_exists && !path.exists()
+			} else {
+				path.is_file()
+			};
+
+			// Note: Bundles sharing a src_dir can match the same file.
+			if in_bundle && exists && !found.iter().any(|f| f.path == path) {
+				found.push(ResolvedPath {
+					bundle,
+					path,
+					rel_path,
+				});
+			}
+		}
+	}
+
+	match found.len() {
+		0 => Err(Error::PatchFileNotInBundles(patch_path.to_string())),
+		1 => Ok(found.remove(0)),
+		_ => {
+			let matches: Vec<String> = found
+				.iter()
+				.map(|f| format!("[{}] {}", f.bundle.bundle_name, f.rel_path))
+				.collect();
+			Err(Error::PatchPathInvalid {
+				path: patch_path.to_string(),
+				reason: format!("matches several files: {}", matches.join(", ")),
+			})
+		}
+	}
+}
+
+fn as_strs(globs: &[String]) -> Vec<&str> {
+	globs.iter().map(AsRef::as_ref).collect()
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use std::fs;
+
+	/// Creates a fresh test directory (under the system temp dir) with the `files`.
+	fn test_dir(name: &str, files: &[&str]) -> Result<PathBuf> {
+		let dir = std::env::temp_dir().join(format!("buddy-test-apply-{name}"));
+		if dir.exists() {
+			fs::remove_dir_all(&dir)?;
+		}
+		for file in files {
+			let file = dir.join(file);
+			fs::create_dir_all(file.parent().ok_or("no parent")?)?;
+			fs::write(file, "")?;
+		}
+		Ok(dir)
+	}
+
+	fn bundle(name: &str, src_dir: &str) -> Result<FileBundle> {
+		let toml = format!(
+			"bundle_name = '{name}'\nsrc_dir = '{src_dir}'\ndst_ext = 'rs'\nsrc_globs = ['**/*.rs']"
+		);
+		Ok(toml::from_str(&toml)?)
+	}
+
+	#[test]
+	fn test_resolve_patch_path_same_file_name_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir(
+			"same-name",
+			&["crates/cli/src/main.rs", "crates/lib/src/main.rs"],
+		)?;
+		let bundles =
+			vec![bundle("cli", "crates/cli")?, bundle("lib", "crates/lib")?];
+
+		// -- Exec
+		let cli =
+			resolve_patch_path(&dir, &bundles, "crates/cli/src/main.rs", false)?;
+		let lib = resolve_patch_path(&dir, &bundles, "lib/src/main.rs", false)?;
+
+		// -- Check
+		assert_eq!(cli.bundle.bundle_name, "cli");
+		assert_eq!(cli.rel_path, "src/main.rs");
+		assert_eq!(lib.bundle.bundle_name, "lib");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_resolve_patch_path_ambiguous_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir(
+			"ambiguous",
+			&["crates/cli/src/main.rs", "crates/lib/src/main.rs"],
+		)?;
+		let bundles =
+			vec![bundle("cli", "crates/cli")?, bundle("lib", "crates/lib")?];
+
+		// -- Exec
+		let res = resolve_patch_path(&dir, &bundles, "src/main.rs", false);
+
+		// -- Check
+		assert!(matches!(res, Err(crate::Error::PatchPathInvalid { .. })));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_resolve_patch_path_other_crate_err() -> Result<()> {
+		// -- Setup & Fixtures
+		// Only the `lib` crate is bundled, so a `cli` patch must not go to `lib`.
+		let dir = test_dir(
+			"other-crate",
+			&["crates/cli/src/main.rs", "crates/lib/src/main.rs"],
+		)?;
+		let bundles = vec![bundle("lib", "crates/lib")?];
+
+		// -- Exec
+		let res =
+			resolve_patch_path(&dir, &bundles, "crates/cli/src/main.rs", false);
+
+		// -- Check
+		assert!(matches!(res, Err(crate::Error::PatchFileNotInBundles(_))));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_resolve_patch_path_new_file_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir("new-file", &["crates/lib/src/lib.rs"])?;
+		let bundles = vec![bundle("lib", "crates/lib")?];
+
+		// -- Exec
+		let res = resolve_patch_path(&dir, &bundles, "crates/lib/src/new.rs", true)?;
+		let not_rs =
+			resolve_patch_path(&dir, &bundles, "crates/lib/src/new.txt", true);
+
+		// -- Check
+		assert_eq!(res.rel_path, "src/new.rs");
+		assert!(not_rs.is_err());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
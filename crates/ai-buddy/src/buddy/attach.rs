@@ -0,0 +1,234 @@
+//! Local files attached to a single chat message (see `Buddy::chat_with_files`).
+//!
+//! Depending on the `[attach]` mode of the `buddy.toml`, the files are either uploaded and
+//! attached to the message (and deleted after the run), or inlined in the message text.
+
+use crate::buddy::config::Attach;
+use crate::utils::redact::Redactor;
+use crate::utils::tokens::approx_tokens;
+use crate::{Error, Result};
+use simple_fs::{list_files, SPath};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of files attached to a message (Assistants API limit).
+const MAX_UPLOAD_FILES: usize = 10;
+
+/// File extensions supported by the retrieval tool. The other files are uploaded
+/// as a `.txt` copy.
+const RETRIEVAL_EXTS: &[&str] = &[
+	"c", "cpp", "css", "docx", "html", "java", "js", "json", "md", "pdf", "php",
+	"pptx", "py", "rb", "sh", "tex", "ts", "txt",
+];
+
+// region:    --- Attachment
+
+pub(super) struct Attachment {
+	pub path: PathBuf,
+	/// `None` when the file is not UTF-8 text.
+	pub content: Option<String>,
+	/// Whether secrets were masked in the `content` (then uploaded instead of the file).
+	pub redacted: bool,
+}
+
+impl Attachment {
+	pub fn tokens(&self) -> u64 {
+		self.content
+			.as_deref()
+			.map(approx_tokens)
+			.unwrap_or_default()
+	}
+
+	/// Masks the secrets of the text content, and returns the number of secrets masked.
+	///
+	/// Note: The files not in UTF-8 (e.g., PDF) are uploaded as is.
+	pub fn redact(&mut self, redactor: &Redactor) -> usize {
+		let count = self
+			.content
+			.as_mut()
+			.map(|content| redactor.redact(content))
+			.unwrap_or_default();
+		self.redacted |= count > 0;
+		count
+	}
+
+	/// Returns the file to upload, and whether it is a temporary copy (in `tmp_dir`)
+	/// to remove after the run.
+	///
+	/// The copy is the redacted content when secrets were masked.
+	pub fn upload_file(&self, tmp_dir: &Path) -> Result<(SPath, bool)> {
+		let ext = self.path.extension().and_then(|e| e.to_str());
+		let supported = ext.is_some_and(|ext| {
+			RETRIEVAL_EXTS.contains(&ext.to_lowercase().as_str())
+		});
+		if supported && !self.redacted {
+			return Ok((SPath::from_path(&self.path)?, false));
+		}
+
+		let file_name = self
+			.path
+			.file_name()
+			.map(|n| n.to_string_lossy().to_string())
+			.unwrap_or_default();
+		let file_name = if supported {
+			file_name
+		} else {
+			format!("{file_name}.txt")
+		};
+		let tmp_file = tmp_dir.join(file_name);
+		match self.content.as_deref().filter(|_| self.redacted) {
+			Some(content) => fs::write(&tmp_file, content)?,
+			None => {
+				fs::copy(&self.path, &tmp_file)?;
+			}
+		}
+
+		Ok((SPath::from_path(tmp_file)?, true))
+	}
+}
+
+/// Loads the files to attach, and checks the `[attach]` limits.
+pub(super) fn load_attachments(
+	files: &[PathBuf],
+	attach: &Attach,
+	is_upload: bool,
+) -> Result<Vec<Attachment>> {
+	if is_upload && files.len() > MAX_UPLOAD_FILES {
+		return Err(Error::AttachTooManyFiles {
+			count: files.len(),
+			max: MAX_UPLOAD_FILES,
+		});
+	}
+
+	let max_file = attach.max_file_kb * 1024;
+	let max_total = attach.max_total_kb * 1024;
+	let mut total = 0;
+	let mut attachments = Vec::with_capacity(files.len());
+
+	for path in files {
+		let path_str = || path.to_string_lossy().to_string();
+
+		let size = fs::metadata(path)?.len();
+		if size > max_file {
+			return Err(Error::AttachFileTooLarge {
+				path: path_str(),
+				size,
+				max: max_file,
+			});
+		}
+		total += size;
+		if total > max_total {
+			return Err(Error::AttachTooLarge {
+				size: total,
+				max: max_total,
+			});
+		}
+
+		let content = String::from_utf8(fs::read(path)?).ok();
+		if content.is_none() && !is_upload {
+			return Err(Error::AttachNotText(path_str()));
+		}
+
+		attachments.push(Attachment {
+			path: path.clone(),
+			content,
+			redacted: false,
+		});
+	}
+
+	Ok(attachments)
+}
+
+/// The message with the attachment contents inlined (`Inline` mode).
+pub(super) fn inline_msg(msg: &str, attachments: &[Attachment]) -> String {
+	let mut res = msg.to_string();
+	for attachment in attachments {
+		let ext = attachment
+			.path
+			.extension()
+			.and_then(|e| e.to_str())
+			.unwrap_or_default();
+		let content = attachment.content.as_deref().unwrap_or_default();
+		res.push_str(&format!(
+			"\n\n==== file path: {}\n```{ext}\n{}\n```",
+			attachment.path.to_string_lossy(),
+			content.trim_end()
+		));
+	}
+	res
+}
+
+// endregion: --- Attachment
+
+/// Returns the files for a file path, a directory (all its files), or a glob
+/// (relative to the current directory).
+pub fn list_attach_files(path_or_glob: &str) -> Result<Vec<PathBuf>> {
+	let path = Path::new(path_or_glob);
+	if path.is_file() {
+		return Ok(vec![path.to_path_buf()]);
+	}
+
+	let files = if path.is_dir() {
+		list_files(path, Some(&["**/*"]), None)?
+	} else {
+		// Note: The globs are matched on the walked paths, so the walk starts from
+		//       the glob base directory (e.g., `src` for `src/**/*.rs`).
+		let base: Vec<&str> = path_or_glob
+			.split('/')
+			.take_while(|part| !part.contains(['*', '?', '[', '{']))
+			.collect();
+		if base.is_empty() {
+			list_files(".", Some(&[&format!("./{path_or_glob}")]), None)?
+		} else {
+			list_files(base.join("/"), Some(&[path_or_glob]), None)?
+		}
+	};
+	if files.is_empty() {
+		return Err(Error::AttachNoFiles(path_or_glob.to_string()));
+	}
+
+	Ok(files.into_iter().map(|f| f.path().to_path_buf()).collect())
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_attachment_redacted_upload_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join("buddy-test-attach-redact");
+		let tmp_dir = dir.join("tmp");
+		fs::create_dir_all(&tmp_dir)?;
+		let key = format!("sk-proj-{}", "a1b2c3d4".repeat(4));
+		let file = dir.join("config.rs");
+		fs::write(&file, format!("let key = \"{key}\";\n"))?;
+		let mut attachment = Attachment {
+			path: file.clone(),
+			content: Some(fs::read_to_string(&file)?),
+			redacted: false,
+		};
+
+		// -- Exec
+		let count = attachment.redact(&Redactor::new(&[])?);
+		let (upload_file, is_tmp) = attachment.upload_file(&tmp_dir)?;
+
+		// -- Check
+		assert_eq!(count, 1);
+		assert!(is_tmp);
+		assert_eq!(upload_file.file_name(), "config.rs.txt");
+		let uploaded = fs::read_to_string(&upload_file)?;
+		assert!(!uploaded.contains(&key), "{uploaded}");
+		// The original file is not changed.
+		assert!(fs::read_to_string(&file)?.contains(&key));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
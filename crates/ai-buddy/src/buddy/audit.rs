@@ -0,0 +1,52 @@
+//! Append-only audit log of the files uploaded to the AI provider (`.buddy/audit.jsonl`).
+//!
+//! One JSON line per upload, so that "which of our files were sent" can be answered
+//! from the local files and their hashes.
+
+use crate::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, Serialize)]
+pub(super) struct AuditEntry {
+	/// RFC 3339 local time of the upload.
+	pub timestamp: String,
+	pub asst_id: String,
+	/// `None` for a file attached to a single message.
+	pub bundle_name: Option<String>,
+	/// The uploaded file name.
+	pub file_name: String,
+	/// The remote file id.
+	pub file_id: String,
+	/// The uploaded size, in bytes.
+	pub size: u64,
+	/// The SHA-256 of the uploaded content.
+	pub sha256: String,
+	/// The local source files of the upload (e.g., the files of a bundle).
+	pub sources: Vec<AuditSource>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct AuditSource {
+	pub path: String,
+	/// The SHA-256 of the source file content (before any redaction).
+	pub sha256: String,
+}
+
+/// Appends the entry as a JSON line to the audit file (created if missing).
+pub(super) fn append_audit(file: &Path, entry: &AuditEntry) -> Result<()> {
+	let line = serde_json::to_string(entry).map_err(io::Error::from)?;
+
+	let mut writer = OpenOptions::new().create(true).append(true).open(file)?;
+	writeln!(writer, "{line}")?;
+
+	Ok(())
+}
+
+/// Returns the lowercase hex SHA-256 of the bytes.
+pub(super) fn sha256_hex(bytes: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,129 @@
+//! Budget guardrails, checked before a chat message or a file upload is sent.
+//!
+//! The limits come from the `[budget]` section of the `buddy.toml`, and are compared
+//! against the `.buddy/usage.json` store plus the estimate of the pending request.
+
+use crate::buddy::config::Budget;
+use crate::buddy::usage::UsageStore;
+use serde::Serialize;
+use std::fmt;
+
+// region:    --- Types
+
+/// Estimated tokens and cost of a pending request.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Estimate {
+	pub tokens: u64,
+	pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+	DailyTokens(u64),
+	MonthlyTokens(u64),
+	DailyUsd(f64),
+	MonthlyUsd(f64),
+	MsgTokens(u64),
+}
+
+/// A limit that the pending request would exceed.
+///
+/// `used` and `estimate` are in the unit of the limit (tokens or USD).
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BudgetExceeded {
+	pub limit: BudgetLimit,
+	pub used: f64,
+	pub estimate: f64,
+}
+
+impl fmt::Display for BudgetExceeded {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let Self { used, estimate, .. } = self;
+		match self.limit {
+			BudgetLimit::DailyTokens(max) => write!(
+				f,
+				"daily limit of {max} tokens ({used} used + ~{estimate} estimated)"
+			),
+			BudgetLimit::MonthlyTokens(max) => write!(
+				f,
+				"monthly limit of {max} tokens ({used} used + ~{estimate} estimated)"
+			),
+			BudgetLimit::DailyUsd(max) => write!(
+				f,
+				"daily limit of ${max:.2} (${used:.4} used + ~${estimate:.4} estimated)"
+			),
+			BudgetLimit::MonthlyUsd(max) => write!(
+				f,
+				"monthly limit of ${max:.2} (${used:.4} used + ~${estimate:.4} estimated)"
+			),
+			BudgetLimit::MsgTokens(max) => write!(
+				f,
+				"per-message limit of {max} tokens (~{estimate} estimated)"
+			),
+		}
+	}
+}
+
+// endregion: --- Types
+
+/// Returns the first limit the estimate would exceed, if any.
+///
+/// `is_msg` tells whether the per-message limit applies (chat messages only).
+pub(super) fn check(
+	budget: &Budget,
+	store: &UsageStore,
+	estimate: Estimate,
+	is_msg: bool,
+) -> Option<BudgetExceeded> {
+	let today = store.today();
+	let month = store.month();
+	let tokens = estimate.tokens as f64;
+	let cost = estimate.cost_usd;
+
+	let checks = [
+		(
+			budget
+				.max_msg_tokens
+				.filter(|_| is_msg)
+				.map(BudgetLimit::MsgTokens),
+			0.,
+			tokens,
+		),
+		(
+			budget.daily_tokens.map(BudgetLimit::DailyTokens),
+			today.total_tokens() as f64,
+			tokens,
+		),
+		(
+			budget.monthly_tokens.map(BudgetLimit::MonthlyTokens),
+			month.total_tokens() as f64,
+			tokens,
+		),
+		(
+			budget.daily_usd.map(BudgetLimit::DailyUsd),
+			today.cost_usd,
+			cost,
+		),
+		(
+			budget.monthly_usd.map(BudgetLimit::MonthlyUsd),
+			month.cost_usd,
+			cost,
+		),
+	];
+
+	checks.into_iter().find_map(|(limit, used, estimate)| {
+		let limit = limit?;
+		let max = match limit {
+			BudgetLimit::DailyTokens(max)
+			| BudgetLimit::MonthlyTokens(max)
+			| BudgetLimit::MsgTokens(max) => max as f64,
+			BudgetLimit::DailyUsd(max) | BudgetLimit::MonthlyUsd(max) => max,
+		};
+		(used + estimate > max).then_some(BudgetExceeded {
+			limit,
+			used,
+			estimate,
+		})
+	})
+}
@@ -0,0 +1,663 @@
+//! Listing, reading, and formatting of the `file_bundles` source files.
+//!
+//! Each bundle (or bundle part) starts with a table of contents, followed by the
+//! files, each with a `==== file path:` header in the comment syntax of the `dst_ext`.
+
+use crate::buddy::audit::sha256_hex;
+use crate::buddy::config::{BundleMode, FileBundle, NonUtf8Mode};
+use crate::utils::files::{comment_syntax, lang_name, CommentSyntax};
+use crate::utils::rust_outline::rust_outline;
+use crate::utils::tokens::approx_tokens;
+use crate::Result;
+use chrono::{DateTime, Local};
+use ignore::WalkBuilder;
+use serde::Serialize;
+use simple_fs::{get_glob_set, SFile};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Number of bytes checked for a NUL byte to detect a binary file (same as git).
+const BINARY_CHECK_BYTES: usize = 8000;
+
+// region:    --- Types
+
+/// Why a source file is not in its bundle.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SkipReason {
+	Binary,
+	NotUtf8,
+	TooLarge { size: u64, max: u64 },
+}
+
+impl fmt::Display for SkipReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SkipReason::Binary => write!(f, "binary file"),
+			SkipReason::NotUtf8 => write!(f, "not UTF-8"),
+			SkipReason::TooLarge { size, max } => {
+				write!(f, "{size} bytes, over the {max} bytes limit")
+			}
+		}
+	}
+}
+
+/// A bundle source file, with its text content.
+pub(super) struct Source {
+	/// The path relative to the bundle `src_dir` (with `/` separators).
+	pub rel_path: String,
+	pub modified: Option<DateTime<Local>>,
+	/// The SHA-256 of the file content (before any conversion or redaction).
+	pub sha256: String,
+	pub content: String,
+}
+
+/// The content of a bundle file (or bundle part), and its sources.
+pub(super) struct BundlePart<'a> {
+	pub content: String,
+	pub sources: Vec<&'a Source>,
+}
+
+/// The bundle text of a source file, and its table of contents entry.
+struct Section<'a> {
+	source: &'a Source,
+	toc_entry: String,
+	text: String,
+}
+
+/// A source file not bundled.
+pub(super) struct Skipped {
+	pub file: SFile,
+	pub reason: SkipReason,
+}
+
+// endregion: --- Types
+
+/// Returns the files of `src_dir` matching the bundle globs, in path order.
+///
+/// Notes:
+/// - The `src_globs` and `exclude_globs` are matched on the paths relative to `src_dir`.
+/// - Like `simple_fs::list_files`, the sub directories are walked only when a `src_globs`
+///   has a `**`.
+/// - Unreadable entries are skipped.
+pub(super) fn list_bundle_files(
+	src_dir: &Path,
+	bundle: &FileBundle,
+) -> Result<Vec<SFile>> {
+	let src_globs = get_glob_set(&as_strs(&bundle.src_globs))?;
+	let exclude_globs = get_glob_set(&as_strs(&bundle.exclude_globs))?;
+	let is_recursive = bundle.src_globs.iter().any(|g| g.contains("**"));
+
+	let mut walk_builder = WalkBuilder::new(src_dir);
+	walk_builder
+		.max_depth(if is_recursive { None } else { Some(1) })
+		.hidden(!bundle.include_hidden)
+		.git_ignore(bundle.respect_gitignore)
+		.git_global(bundle.respect_gitignore)
+		.git_exclude(bundle.respect_gitignore)
+		.ignore(bundle.respect_gitignore)
+		.parents(bundle.respect_gitignore)
+		// Note: The `src_dir` might not be in a git repository (e.g., a new project).
+		.require_git(false)
+		.sort_by_file_path(|a, b| a.cmp(b));
+
+	// -- Skip the excluded directories (not walked at all).
+	let dir_excludes = exclude_globs.clone();
+	let exclude_dirs = bundle.exclude_dirs.clone();
+	let root = src_dir.to_path_buf();
+	walk_builder.filter_entry(move |entry| {
+		let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+		if !is_dir || entry.depth() == 0 {
+			return true;
+		}
+		let is_excluded_dir = entry
+			.file_name()
+			.to_str()
+			.is_some_and(|name| exclude_dirs.iter().any(|dir| dir == name));
+		let rel_path = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+		!is_excluded_dir && !dir_excludes.is_match(rel_path)
+	});
+
+	// -- Keep the files matching the globs.
+	let mut files = Vec::new();
+	for entry in walk_builder.build().flatten() {
+		if !entry.file_type().is_some_and(|t| t.is_file()) {
+			continue;
+		}
+		let path = entry.path();
+		let rel_path = path.strip_prefix(src_dir).unwrap_or(path);
+		if src_globs.is_match(rel_path) && !exclude_globs.is_match(rel_path) {
+			files.push(SFile::from_path(path)?);
+		}
+	}
+
+	Ok(files)
+}
+
+/// Reads the source files of a bundle, and returns them with the skipped files
+/// (per the bundle `max_file_bytes` and `non_utf8` settings).
+pub(super) fn read_sources(
+	src_dir: &Path,
+	files: Vec<SFile>,
+	bundle: &FileBundle,
+) -> Result<(Vec<Source>, Vec<Skipped>)> {
+	let mut sources = Vec::with_capacity(files.len());
+	let mut skipped = Vec::new();
+
+	for file in files {
+		let metadata = fs::metadata(&file)?;
+
+		// -- Check the size before reading the file.
+		if let Some(max) = bundle.max_file_bytes {
+			let size = metadata.len();
+			if size > max {
+				skipped.push(Skipped {
+					file,
+					reason: SkipReason::TooLarge { size, max },
+				});
+				continue;
+			}
+		}
+
+		let bytes = fs::read(&file)?;
+		let sha256 = sha256_hex(&bytes);
+		let head = &bytes[..bytes.len().min(BINARY_CHECK_BYTES)];
+		if head.contains(&0) {
+			skipped.push(Skipped {
+				file,
+				reason: SkipReason::Binary,
+			});
+			continue;
+		}
+
+		let content = match String::from_utf8(bytes) {
+			Ok(content) => content,
+			Err(err) => match bundle.non_utf8 {
+				NonUtf8Mode::Lossy => {
+					String::from_utf8_lossy(err.as_bytes()).into_owned()
+				}
+				NonUtf8Mode::Skip => {
+					skipped.push(Skipped {
+						file,
+						reason: SkipReason::NotUtf8,
+					});
+					continue;
+				}
+			},
+		};
+
+		let path = file.path();
+		let rel_path = path
+			.strip_prefix(src_dir)
+			.unwrap_or(path)
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+		let modified = metadata.modified().ok().map(DateTime::<Local>::from);
+
+		sources.push(Source {
+			rel_path,
+			modified,
+			sha256,
+			content,
+		});
+	}
+
+	Ok((sources, skipped))
+}
+
+/// Returns the bundle file contents, split into parts when over the bundle
+/// `max_bundle_bytes` or `max_bundle_tokens` limits (one part otherwise).
+///
+/// Notes:
+/// - A file is never split, so a file over the limits has its own part.
+/// - The limits apply to the file sections and table of contents entries, not to
+///   the few header lines of a part.
+pub(super) fn bundle_parts<'a>(
+	sources: &'a [Source],
+	bundle: &FileBundle,
+) -> Vec<BundlePart<'a>> {
+	let comment = comment_syntax(&bundle.dst_ext);
+	let sections: Vec<Section> = sources
+		.iter()
+		.map(|source| file_section(source, bundle, comment))
+		.collect();
+
+	// -- Group the sections under the limits.
+	let mut parts: Vec<Vec<&Section>> = Vec::new();
+	let mut part: Vec<&Section> = Vec::new();
+	let (mut part_bytes, mut part_tokens) = (0, 0);
+
+	for section in sections.iter() {
+		let bytes = (section.toc_entry.len() + section.text.len()) as u64;
+		let tokens =
+			approx_tokens(&section.toc_entry) + approx_tokens(&section.text);
+		let is_over = bundle
+			.max_bundle_bytes
+			.is_some_and(|max| part_bytes + bytes > max)
+			|| bundle
+				.max_bundle_tokens
+				.is_some_and(|max| part_tokens + tokens > max);
+
+		if is_over && !part.is_empty() {
+			parts.push(std::mem::take(&mut part));
+			(part_bytes, part_tokens) = (0, 0);
+		}
+		part.push(section);
+		part_bytes += bytes;
+		part_tokens += tokens;
+	}
+	if !part.is_empty() {
+		parts.push(part);
+	}
+
+	// -- Build each part content, with its table of contents.
+	let count = parts.len();
+	parts
+		.into_iter()
+		.enumerate()
+		.map(|(idx, sections)| {
+			let title = if count > 1 {
+				format!(
+					"==== bundle: {} (part {} of {count})",
+					bundle.bundle_name,
+					idx + 1
+				)
+			} else {
+				format!("==== bundle: {}", bundle.bundle_name)
+			};
+
+			let mut content = comment.line(&title);
+			content.push('\n');
+			content
+				.push_str(&comment.line(&format!("==== files: {}", sections.len())));
+			content.push('\n');
+			for section in sections.iter() {
+				content.push_str(&section.toc_entry);
+			}
+			for section in sections.iter() {
+				content.push_str(&section.text);
+			}
+			BundlePart {
+				content,
+				sources: sections.iter().map(|s| s.source).collect(),
+			}
+		})
+		.collect()
+}
+
+/// Returns the bundle section of a source file (header, and content lines).
+///
+/// Note: In the `outline` mode, the lines of a Rust file are not numbered (the outline
+///       lines do not match the file lines), and a file that cannot be parsed is
+///       bundled in full.
+fn file_section<'a>(
+	source: &'a Source,
+	bundle: &FileBundle,
+	comment: CommentSyntax,
+) -> Section<'a> {
+	let num_lines = source.content.lines().count();
+	let ext = Path::new(&source.rel_path)
+		.extension()
+		.and_then(|e| e.to_str())
+		.unwrap_or_default();
+
+	let outline = if bundle.mode == BundleMode::Outline && ext == "rs" {
+		rust_outline(&source.content, bundle.outline_body_lines)
+	} else {
+		None
+	};
+	let (content, line_numbers, kind) = match outline.as_deref() {
+		Some(outline) => (outline, false, ", outline"),
+		None => (source.content.as_str(), bundle.line_numbers, ""),
+	};
+	let modified = source
+		.modified
+		.map(|m| m.format("%Y-%m-%d %H:%M").to_string())
+		.unwrap_or_else(|| "unknown".to_string());
+
+	let toc_entry =
+		comment.line(&format!("  {} ({num_lines} lines)", source.rel_path));
+
+	let mut text = format!(
+		"\n{}\n{}\n\n",
+		comment.line(&format!("==== file path: {}", source.rel_path)),
+		comment.line(&format!(
+			"language: {}{kind}, lines: {num_lines}, modified: {modified}",
+			lang_name(ext)
+		))
+	);
+	let width = num_lines.to_string().len();
+	for (idx, line) in content.lines().enumerate() {
+		if line_numbers {
+			text.push_str(&format!("{:>width$} | ", idx + 1));
+		}
+		text.push_str(line);
+		text.push('\n');
+	}
+	text.push_str("\n\n");
+
+	Section {
+		source,
+		toc_entry: format!("{toc_entry}\n"),
+		text,
+	}
+}
+
+/// Returns true if `rel_path` (relative to the bundle `src_dir`) is excluded by the
+/// bundle `exclude_globs` or `exclude_dirs`.
+pub(super) fn is_excluded(bundle: &FileBundle, rel_path: &str) -> Result<bool> {
+	let exclude_globs = get_glob_set(&as_strs(&bundle.exclude_globs))?;
+	let mut dirs = rel_path.split('/').rev().skip(1);
+	let in_excluded_dir =
+		dirs.any(|dir| bundle.exclude_dirs.iter().any(|d| d == dir));
+	Ok(in_excluded_dir || exclude_globs.is_match(rel_path))
+}
+
+/// Returns true if `file_name` is a file of a bundle (one of the file name `prefixes`),
+/// but not one of the `generated` bundle files anymore (e.g., a part left over after
+/// the bundle got smaller).
+pub(super) fn is_stale_file(
+	file_name: &str,
+	prefixes: &[String],
+	generated: &[&str],
+) -> bool {
+	prefixes.iter().any(|p| file_name.starts_with(p.as_str()))
+		&& !generated.contains(&file_name)
+}
+
+fn as_strs(globs: &[String]) -> Vec<&str> {
+	globs.iter().map(AsRef::as_ref).collect()
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use std::path::PathBuf;
+
+	/// Creates a fresh test directory (under the system temp dir) with the `files`.
+	fn test_dir(name: &str, files: &[(&str, &[u8])]) -> Result<PathBuf> {
+		let dir = std::env::temp_dir().join(format!("buddy-test-bundle-{name}"));
+		if dir.exists() {
+			fs::remove_dir_all(&dir)?;
+		}
+		for (file, content) in files {
+			let file = dir.join(file);
+			fs::create_dir_all(file.parent().ok_or("no parent")?)?;
+			fs::write(file, content)?;
+		}
+		Ok(dir)
+	}
+
+	/// The `bundle_name = 'src'` bundle with `extra` toml lines.
+	fn bundle(extra: &str) -> Result<FileBundle> {
+		let toml = format!(
+			"bundle_name = 'src'\nsrc_dir = 'src'\ndst_ext = 'rs'\nsrc_globs = ['**/*.rs']\n{extra}"
+		);
+		Ok(toml::from_str(&toml)?)
+	}
+
+	fn source(rel_path: &str, content: &str) -> Source {
+		Source {
+			rel_path: rel_path.to_string(),
+			modified: None,
+			sha256: sha256_hex(content.as_bytes()),
+			content: content.to_string(),
+		}
+	}
+
+	fn rel_paths(src_dir: &Path, files: &[SFile]) -> Vec<String> {
+		files
+			.iter()
+			.map(|f| {
+				let path = f.path().strip_prefix(src_dir).unwrap_or(f.path());
+				path.to_string_lossy().replace('\\', "/")
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_list_bundle_files_relative_globs_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir(
+			"globs",
+			&[
+				("lib.rs", b""),
+				("gen/model.rs", b""),
+				("sub/mod.rs", b""),
+				("sub/target/out.rs", b""),
+				("target/debug/build.rs", b""),
+				("notes.md", b""),
+			],
+		)?;
+		let bundle = bundle("exclude_globs = ['gen/**']")?;
+
+		// -- Exec
+		let files = list_bundle_files(&dir, &bundle)?;
+
+		// -- Check
+		// Note: The globs are relative to the src_dir, and `target` is excluded at any depth.
+		assert_eq!(rel_paths(&dir, &files), ["lib.rs", "sub/mod.rs"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_list_bundle_files_gitignore_without_git_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir(
+			"gitignore",
+			&[
+				(".gitignore", b"ignored.rs\n"),
+				("lib.rs", b""),
+				("ignored.rs", b""),
+			],
+		)?;
+		let bundle = bundle("")?;
+
+		// -- Exec
+		let files = list_bundle_files(&dir, &bundle)?;
+
+		// -- Check
+		assert_eq!(rel_paths(&dir, &files), ["lib.rs"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_read_sources_skipped_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir(
+			"skipped",
+			&[
+				("binary.rs", b"fn main() {}\0"),
+				("latin1.rs", b"// caf\xe9\n"),
+				("large.rs", &[b'a'; 200]),
+				("ok.rs", b"fn main() {}\n"),
+			],
+		)?;
+		let bundle = bundle("max_file_bytes = 100")?;
+		let files = list_bundle_files(&dir, &bundle)?;
+
+		// -- Exec
+		let (sources, skipped) = read_sources(&dir, files, &bundle)?;
+
+		// -- Check
+		let rel_paths: Vec<&str> =
+			sources.iter().map(|s| s.rel_path.as_str()).collect();
+		assert_eq!(rel_paths, ["ok.rs"]);
+		let reasons: Vec<String> =
+			skipped.iter().map(|s| s.reason.to_string()).collect();
+		assert_eq!(
+			reasons,
+			[
+				"binary file",
+				"200 bytes, over the 100 bytes limit",
+				"not UTF-8"
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_read_sources_lossy_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = test_dir("lossy", &[("latin1.rs", b"// caf\xe9\n")])?;
+		let bundle = bundle("non_utf8 = 'lossy'")?;
+		let files = list_bundle_files(&dir, &bundle)?;
+
+		// -- Exec
+		let (sources, skipped) = read_sources(&dir, files, &bundle)?;
+
+		// -- Check
+		assert!(skipped.is_empty());
+		assert_eq!(sources[0].content, "// caf\u{FFFD}\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bundle_parts_toc_and_sections_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let sources = vec![
+			source("main.rs", "fn main() {}\n"),
+			source("lib/mod.rs", "mod a;\nmod b;\n"),
+		];
+		let bundle = bundle("line_numbers = true")?;
+
+		// -- Exec
+		let parts = bundle_parts(&sources, &bundle);
+
+		// -- Check
+		assert_eq!(parts.len(), 1);
+		let content = &parts[0].content;
+		assert!(content.starts_with(
+			"// ==== bundle: src\n// ==== files: 2\n//   main.rs (1 lines)\n//   lib/mod.rs (2 lines)\n"
+		));
+		assert!(content.contains(
+			"// ==== file path: lib/mod.rs\n// language: rust, lines: 2, modified: unknown\n\n1 | mod a;\n2 | mod b;\n"
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bundle_parts_split_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let body = "// some code\n".repeat(20);
+		let sources = vec![
+			source("a.rs", &body),
+			source("b.rs", &body),
+			source("c.rs", &body),
+		];
+		// Note: Two file sections fit under the limit, not three.
+		let bundle = bundle("max_bundle_bytes = 800")?;
+
+		// -- Exec
+		let parts = bundle_parts(&sources, &bundle);
+
+		// -- Check
+		assert_eq!(parts.len(), 2);
+		assert!(parts[0]
+			.content
+			.starts_with("// ==== bundle: src (part 1 of 2)\n// ==== files: 2\n"));
+		assert!(parts[1]
+			.content
+			.starts_with("// ==== bundle: src (part 2 of 2)\n// ==== files: 1\n"));
+		let part_sources: Vec<Vec<&str>> = parts
+			.iter()
+			.map(|p| p.sources.iter().map(|s| s.rel_path.as_str()).collect())
+			.collect();
+		assert_eq!(part_sources, [vec!["a.rs", "b.rs"], vec!["c.rs"]]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bundle_parts_file_over_limit_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let sources = vec![
+			source("big.rs", &"// big\n".repeat(100)),
+			source("small.rs", "// small\n"),
+		];
+		let bundle = bundle("max_bundle_bytes = 100")?;
+
+		// -- Exec
+		let parts = bundle_parts(&sources, &bundle);
+
+		// -- Check
+		// Note: A file is never split, so the big one has its own part.
+		assert_eq!(parts.len(), 2);
+		assert_eq!(parts[0].sources[0].rel_path, "big.rs");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bundle_parts_outline_fallback_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let sources = vec![
+			source("ok.rs", "use std::fs;\npub fn run() {}\n"),
+			source("broken.rs", "use std::fs;\npub fn broken( {\n"),
+		];
+		let bundle = bundle("mode = 'outline'")?;
+
+		// -- Exec
+		let parts = bundle_parts(&sources, &bundle);
+
+		// -- Check
+		let content = &parts[0].content;
+		assert!(content.contains("// language: rust, outline, lines: 2"));
+		// Note: The file that cannot be parsed is bundled in full.
+		assert!(content.contains(
+			"// language: rust, lines: 2, modified: unknown\n\nuse std::fs;\npub fn broken( {\n"
+		));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_is_stale_file_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let prefixes = vec!["buddy-src-bundle-asst_1".to_string()];
+		let generated = [
+			"buddy-src-bundle-asst_1-part1.rs",
+			"buddy-src-bundle-asst_1-part2.rs",
+		];
+
+		// -- Exec & Check
+		assert!(is_stale_file(
+			"buddy-src-bundle-asst_1-part3.rs",
+			&prefixes,
+			&generated
+		));
+		assert!(is_stale_file(
+			"buddy-src-bundle-asst_1.rs",
+			&prefixes,
+			&generated
+		));
+		assert!(!is_stale_file(
+			"buddy-src-bundle-asst_1-part2.rs",
+			&prefixes,
+			&generated
+		));
+		// Another bundle.
+		assert!(!is_stale_file(
+			"buddy-docs-bundle-asst_1-part3.md",
+			&prefixes,
+			&generated
+		));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
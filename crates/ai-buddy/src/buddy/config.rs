@@ -1,5 +1,9 @@
 use crate::ais::asst;
-use serde::Deserialize;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use simple_fs::read_to_string;
+use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, Deserialize)]
 pub(super) struct Config {
@@ -7,6 +11,29 @@ pub(super) struct Config {
 	pub model: String,
 	pub instructions_file: String,
 	pub file_bundles: Vec<FileBundle>,
+	/// Price table by model name (e.g., `[prices."gpt-3.5-turbo-1106"]`).
+	#[serde(default)]
+	pub prices: HashMap<String, ModelPrice>,
+	/// Optional spending limits (`[budget]`).
+	pub budget: Option<Budget>,
+	/// Files attached to a single message (`[attach]`).
+	#[serde(default)]
+	pub attach: Attach,
+	/// Secret redaction of the bundles and `/file` attachments before upload
+	/// (`[redact]`).
+	#[serde(default)]
+	pub redact: Redact,
+}
+
+impl Config {
+	/// Loads the `buddy.toml` file, with the parse error location on invalid content.
+	pub fn load(file: &Path) -> Result<Config> {
+		let content = read_to_string(file).map_err(Error::simple_fs_at(file))?;
+		toml::from_str(&content).map_err(|cause| Error::ConfigInvalid {
+			path: file.to_string_lossy().to_string(),
+			cause,
+		})
+	}
 }
 
 #[derive(Debug, Deserialize)]
@@ -14,7 +41,178 @@ pub(super) struct FileBundle {
 	pub bundle_name: String,
 	pub src_dir: String,
 	pub dst_ext: String,
+	/// Globs of the files to bundle, relative to `src_dir` (e.g., `**/*.rs`).
+	///
+	/// Note: Before the `exclude_globs`, they were matched on the whole paths
+	///       (e.g., `../crates/**/*.rs`), which do not match anymore.
 	pub src_globs: Vec<String>,
+	/// Globs of the files and directories to skip (relative to `src_dir`).
+	#[serde(default)]
+	pub exclude_globs: Vec<String>,
+	/// Names of the directories never walked, at any depth
+	/// (default `["target", "node_modules"]`).
+	#[serde(default = "default_exclude_dirs")]
+	pub exclude_dirs: Vec<String>,
+	/// Skip the files ignored by the `.gitignore`/`.ignore` files (default `true`).
+	#[serde(default = "default_true")]
+	pub respect_gitignore: bool,
+	/// Include the hidden files and directories (default `false`).
+	#[serde(default)]
+	pub include_hidden: bool,
+	/// Maximum size of a bundle file, split into numbered part files above it.
+	pub max_bundle_bytes: Option<u64>,
+	/// Maximum estimated tokens of a bundle file, split into numbered part files above it.
+	pub max_bundle_tokens: Option<u64>,
+	/// Maximum size of a source file, skipped above it.
+	pub max_file_bytes: Option<u64>,
+	/// Prefix the lines of the bundled files with their line numbers (default `false`).
+	#[serde(default)]
+	pub line_numbers: bool,
+	/// How the files are bundled (default `full`).
+	#[serde(default)]
+	pub mode: BundleMode,
+	/// For the `outline` mode, the function bodies up to this number of lines are kept.
+	#[serde(default = "default_outline_body_lines")]
+	pub outline_body_lines: usize,
+	/// What to do with the text files not in UTF-8 (the binary files are always skipped).
+	#[serde(default)]
+	pub non_utf8: NonUtf8Mode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum NonUtf8Mode {
+	/// Skip the file (with a `BuddyEvent::BundleFileSkipped`).
+	#[default]
+	Skip,
+	/// Bundle the file with the invalid sequences replaced by `�`.
+	Lossy,
+}
+
+fn default_true() -> bool {
+	true
+}
+
+fn default_exclude_dirs() -> Vec<String> {
+	vec!["target".to_string(), "node_modules".to_string()]
+}
+
+fn default_outline_body_lines() -> usize {
+	5
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum BundleMode {
+	/// The file contents.
+	#[default]
+	Full,
+	/// For the `.rs` files, an outline of the items (signatures, types, and doc comments)
+	/// with the long function bodies elided. The other files are bundled in full.
+	Outline,
+}
+
+/// Price of a model, in USD per 1K tokens.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModelPrice {
+	pub input_per_1k: f64,
+	pub output_per_1k: f64,
+}
+
+impl ModelPrice {
+	pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+		(prompt_tokens as f64 * self.input_per_1k
+			+ completion_tokens as f64 * self.output_per_1k)
+			/ 1000.
+	}
+}
+
+/// Spending limits. All limits are optional, and tokens/costs are local estimates.
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct Budget {
+	#[serde(default)]
+	pub policy: BudgetPolicy,
+	pub daily_tokens: Option<u64>,
+	pub monthly_tokens: Option<u64>,
+	pub daily_usd: Option<f64>,
+	pub monthly_usd: Option<f64>,
+	/// Maximum prompt tokens for a single chat message (instructions and thread included).
+	pub max_msg_tokens: Option<u64>,
+}
+
+/// What to do when a request would exceed a budget limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPolicy {
+	/// Refuse with `Error::BudgetExceeded`.
+	Block,
+	/// Proceed, and send a `BuddyEvent::BudgetWarning`.
+	#[default]
+	Warn,
+	/// Refuse with `Error::BudgetConfirmRequired` unless called with the `_confirmed` variant.
+	/// The uploads of `Buddy::init_from_dir` and `Buddy::watch` are skipped
+	/// (see `BuddyEvent::BudgetUploadSkipped`).
+	Confirm,
+}
+
+/// How the files of `Buddy::chat_with_files` are given to the assistant, and their limits.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(super) struct Attach {
+	pub mode: AttachMode,
+	pub max_file_kb: u64,
+	pub max_total_kb: u64,
+}
+
+impl Default for Attach {
+	fn default() -> Self {
+		Self {
+			mode: AttachMode::default(),
+			max_file_kb: 512,
+			max_total_kb: 2048,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachMode {
+	/// Upload the files and attach them to the message (deleted after the run).
+	#[default]
+	Upload,
+	/// Inline the file contents in the message text (for chat-completions like providers).
+	Inline,
+}
+
+/// Secret detection in the bundled files, on top of the built-in patterns
+/// (API keys, AWS keys, private keys, and `.env` style assignments).
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(super) struct Redact {
+	pub enabled: bool,
+	pub policy: RedactPolicy,
+	/// Custom regexes (only the first capture group is masked when there is one).
+	pub patterns: Vec<String>,
+}
+
+impl Default for Redact {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			policy: RedactPolicy::default(),
+			patterns: Vec::new(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum RedactPolicy {
+	/// Mask the secrets, and upload (with a `BuddyEvent::SecretsRedacted` per file).
+	#[default]
+	Mask,
+	/// Refuse to upload with `Error::SecretsFound`.
+	Block,
 }
 
 // region:    --- Froms
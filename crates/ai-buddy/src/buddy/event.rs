@@ -1,8 +1,66 @@
 //! Buddy event
 
-#[derive(Debug, Clone)]
+use crate::buddy::{AttachMode, BudgetExceeded, SkipReason};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
 pub enum BuddyEvent {
 	InstUploaded,
 	ConvLoaded,
 	ConvCreated,
+	ConvNoteAdded,
+	BudgetWarning(BudgetExceeded),
+	/// A bundle upload not done, since over the budget and not confirmed
+	/// (`BudgetPolicy::Confirm`, see `Buddy::upload_files_confirmed`).
+	BudgetUploadSkipped(BudgetExceeded),
+	FilesAttached {
+		mode: AttachMode,
+		files: Vec<String>,
+	},
+	/// Secrets were masked in an attached file.
+	AttachSecretsRedacted {
+		path: String,
+		count: usize,
+	},
+
+	// -- Bundle
+	/// A source file not bundled (the bundle is uploaded without it).
+	BundleFileSkipped {
+		bundle_name: String,
+		path: String,
+		reason: SkipReason,
+	},
+	/// Secrets were masked in a bundled file.
+	SecretsRedacted {
+		bundle_name: String,
+		path: String,
+		count: usize,
+	},
+	/// The bundle is over its size limits, and split into `parts` files.
+	BundleSplit {
+		bundle_name: String,
+		parts: usize,
+	},
+	/// A bundle part file not generated anymore was removed (local and uploaded).
+	BundlePartRemoved {
+		file_name: String,
+	},
+
+	// -- Watch
+	WatchStarted {
+		paths: Vec<String>,
+	},
+	/// The instructions or bundles (by name) affected by a change.
+	WatchChanged {
+		targets: Vec<String>,
+	},
+	BundleUpToDate {
+		bundle_name: String,
+	},
+	/// A re-upload failed (the watch continues).
+	WatchUploadFailed {
+		target: String,
+		cause: String,
+	},
 }
@@ -13,30 +13,67 @@
 
 // region:    --- Modules
 
+mod apply;
+mod attach;
+mod audit;
+mod budget;
+mod bundle;
 mod config;
 mod event;
+mod plan;
+mod usage;
+mod watch;
 
+pub use crate::ais::{
+	Annotation, AnnotationKind, ChatResponse, RunFailure, RunStepInfo, Usage,
+};
+pub use apply::FileChange;
+pub use attach::list_attach_files;
+pub use budget::{BudgetExceeded, BudgetLimit};
+pub use bundle::SkipReason;
+pub use config::{AttachMode, BudgetPolicy};
 pub use event::BuddyEvent;
+pub use plan::{Plan, PlanAction, PlanChange, PlanTarget};
+pub use usage::UsageReport;
 
 use crate::ais::asst::{self};
-use crate::ais::{new_ais_client, AisClient, AsstId, ThreadId};
-use crate::buddy::config::Config;
+use crate::ais::{new_ais_client, AisClient, AsstId, FileId, FileRef, ThreadId};
+use crate::buddy::apply::resolve_patch_path;
+use crate::buddy::attach::{inline_msg, load_attachments, Attachment};
+use crate::buddy::audit::{append_audit, sha256_hex, AuditEntry, AuditSource};
+use crate::buddy::budget::Estimate;
+use crate::buddy::bundle::{
+	bundle_parts, is_stale_file, list_bundle_files, read_sources, Skipped,
+};
+use crate::buddy::config::{Config, FileBundle, ModelPrice, RedactPolicy};
+use crate::buddy::plan::{PlanAction as Action, PlanTarget as Target};
+use crate::buddy::usage::UsageStore;
+use crate::buddy::watch::{spawn_watcher, WatchTarget};
 use tokio::sync::broadcast::Receiver;
 // use crate::event::EventBus;
 use crate::event::{Event, EventBus};
-use crate::utils::files::bundle_to_file;
+use crate::patch::{apply_patch, parse_patch};
+use crate::utils::redact::Redactor;
+use crate::utils::tokens::approx_tokens;
 use crate::{Error, Result};
+use chrono::Local;
 use derive_more::{Deref, From};
 use serde::{Deserialize, Serialize};
 use simple_fs::{
-	ensure_dir, list_files, load_json, load_toml, read_to_string, save_json, SPath,
+	ensure_dir, list_files, load_json, read_to_string, save_json, SFile, SPath,
 };
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::timeout;
 
 // endregion: --- Modules
 
 const BUDDY_TOML: &str = "buddy.toml";
+/// Quiet time after a change before re-uploading (e.g., for a `git checkout`).
+const WATCH_QUIET_MS: u64 = 500;
 
 #[derive(Debug)]
 pub struct Buddy {
@@ -44,7 +81,14 @@ pub struct Buddy {
 	ais_client: AisClient,
 	asst_id: AsstId,
 	config: Config,
+	/// The `InitOptions::model` override of the runs (the assistant keeps its model).
+	run_model: Option<String>,
 	event_bus: EventBus,
+	/// Serializes the bundle uploads (e.g., the watch and `/rf`), from the staging
+	/// to the rename into `.buddy/files/`.
+	upload_lock: Mutex<()>,
+	/// Serializes the `.buddy/usage.json` updates (load, record, and save).
+	usage_lock: std::sync::Mutex<()>,
 }
 
 #[derive(Debug, From, Deref, Deserialize, Serialize)]
@@ -52,11 +96,99 @@ pub struct Conv {
 	thread_id: ThreadId,
 }
 
+/// Options for `Buddy::init_from_dir`.
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+	/// Delete and recreate the assistant (and its files).
+	pub recreate_asst: bool,
+	/// Bundle and upload the `file_bundles` (default `true`).
+	pub upload_files: bool,
+	/// Overrides the `buddy.toml` model for the runs of this buddy only
+	/// (the assistant model is not changed).
+	pub model: Option<String>,
+	/// Print the run polling progress on stderr (default `false`).
+	pub run_progress: bool,
+}
+
+impl Default for InitOptions {
+	fn default() -> Self {
+		Self {
+			recreate_asst: false,
+			upload_files: true,
+			model: None,
+			run_progress: false,
+		}
+	}
+}
+
+/// Which bundle files are re-uploaded when they are already uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reupload {
+	/// None (only the missing ones are uploaded).
+	Missing,
+	/// The ones with a content different from the previous bundling.
+	Changed,
+	/// All of them.
+	All,
+}
+
+impl Reupload {
+	fn from_recreate(recreate: bool) -> Self {
+		if recreate {
+			Reupload::All
+		} else {
+			Reupload::Missing
+		}
+	}
+}
+
+/// A generated bundle file, with its sources for the audit log.
+struct BundleFile {
+	file: SPath,
+	content: String,
+	/// The content last uploaded, from the `.buddy/files/` copy (if any).
+	previous: Option<String>,
+	bundle_name: String,
+	sources: Vec<AuditSource>,
+}
+
+impl BundleFile {
+	fn force_reupload(&self, reupload: Reupload) -> bool {
+		match reupload {
+			Reupload::All => true,
+			Reupload::Changed => {
+				self.previous.as_deref() != Some(self.content.as_str())
+			}
+			// If it does not exist, then we will force a reupload
+			Reupload::Missing => self.previous.is_none(),
+		}
+	}
+}
+
+/// The bundle files generated by `Buddy::generate_bundles`.
+#[derive(Default)]
+struct Bundling {
+	files: Vec<BundleFile>,
+	/// The file name prefixes of the bundles generated (to find their stale parts).
+	prefixes: Vec<String>,
+	/// The files with secrets masked (`{bundle_name}/{rel_path}`).
+	secret_files: Vec<String>,
+}
+
+impl Bundling {
+	/// A part file of one of the bundles, which was not generated anymore.
+	fn is_stale(&self, file_name: &str) -> bool {
+		let generated: Vec<&str> =
+			self.files.iter().map(|f| f.file.file_name()).collect();
+		is_stale_file(file_name, &self.prefixes, &generated)
+	}
+}
+
 /// Constructor functions
 impl Buddy {
 	pub async fn init_from_dir(
 		dir: impl AsRef<Path>,
-		recreate_asst: bool,
+		options: InitOptions,
 		event_bus: Option<EventBus>,
 	) -> Result<Self> {
 		let dir = dir.as_ref();
@@ -64,14 +196,18 @@ impl Buddy {
 		let event_bus = event_bus.unwrap_or_else(EventBus::new);
 
 		// -- Load from the directory
-		let config: Config = load_toml(dir.join(BUDDY_TOML))?;
+		let config = Config::load(&dir.join(BUDDY_TOML))?;
 
 		// -- Get or Create the OpenAI Assistant
-		let ais_client = new_ais_client(event_bus.clone())?;
+		let ais_client = new_ais_client(event_bus.clone())?
+			.with_run_progress(options.run_progress);
 
-		let asst_id =
-			asst::load_or_create(&ais_client, (&config).into(), recreate_asst)
-				.await?;
+		let asst_id = asst::load_or_create(
+			&ais_client,
+			(&config).into(),
+			options.recreate_asst,
+		)
+		.await?;
 
 		// -- Create buddy
 		let buddy = Buddy {
@@ -79,17 +215,178 @@ impl Buddy {
 			ais_client,
 			asst_id,
 			config,
+			run_model: options.model,
 			event_bus,
+			upload_lock: Mutex::new(()),
+			usage_lock: std::sync::Mutex::new(()),
 		};
 
 		// -- Upload instructions
 		buddy.upload_instructions().await?;
 
 		// -- Upload files
-		buddy.upload_files(false).await?;
+		// Note: No one can confirm at construction time, so with the `Confirm` budget
+		//       policy, the upload is skipped (see `upload_files_confirmed`).
+		if options.upload_files {
+			let res = buddy
+				.upload_files_impl(Reupload::Missing, None, false)
+				.await;
+			buddy.skip_unconfirmed_upload(res)?;
+		}
 
 		Ok(buddy)
 	}
+
+	/// Computes what `init_from_dir` with the same arguments, then loading the
+	/// `conv_name` conversation (the default one when `None`), would do, without any
+	/// change (nothing created, updated, uploaded, or deleted, and nothing written
+	/// in `.buddy/`).
+	pub async fn plan(
+		dir: impl AsRef<Path>,
+		options: InitOptions,
+		conv_name: Option<&str>,
+		recreate_conv: bool,
+		event_bus: Option<EventBus>,
+	) -> Result<Plan> {
+		let dir = dir.as_ref();
+
+		let event_bus = event_bus.unwrap_or_else(EventBus::new);
+
+		// -- Load from the directory
+		let config = Config::load(&dir.join(BUDDY_TOML))?;
+
+		let ais_client = new_ais_client(event_bus.clone())?;
+		let mut plan = Plan::new(&config.name);
+
+		// -- Assistant (see `asst::load_or_create`)
+		let asst_obj = asst::first_by_name(&ais_client, &config.name).await?;
+		let asst_obj = match asst_obj {
+			None => {
+				let detail = format!("model {}", config.model);
+				plan.add(
+					Action::Create,
+					Target::Assistant,
+					&config.name,
+					Some(detail),
+				);
+				None
+			}
+			Some(asst_obj) if options.recreate_asst => {
+				let asst_id = AsstId::from(asst_obj.id);
+				let detail = format!("{asst_id}, model {}", config.model);
+				plan.add(
+					Action::Replace,
+					Target::Assistant,
+					&config.name,
+					Some(detail),
+				);
+				let remote_files =
+					asst::get_files_hashmap(&ais_client, &asst_id).await?;
+				let mut file_names: Vec<String> = remote_files.into_keys().collect();
+				file_names.sort();
+				for file_name in file_names {
+					let detail = "deleted with the assistant".to_string();
+					plan.add(
+						Action::Delete,
+						Target::RemoteFile,
+						file_name,
+						Some(detail),
+					);
+				}
+				None
+			}
+			Some(asst_obj) => {
+				plan.asst_id = Some(asst_obj.id.clone());
+				let detail = match options.model.as_deref() {
+					Some(model) if model != asst_obj.model => format!(
+						"{}, model {}, runs with model {model}",
+						asst_obj.id, asst_obj.model
+					),
+					_ => format!("{}, model {}", asst_obj.id, asst_obj.model),
+				};
+				plan.add(
+					Action::Keep,
+					Target::Assistant,
+					&config.name,
+					Some(detail),
+				);
+				Some(asst_obj)
+			}
+		};
+
+		// -- Instructions
+		let inst_file = dir.join(&config.instructions_file);
+		if inst_file.exists() {
+			let inst_content = read_to_string(&inst_file)
+				.map_err(Error::simple_fs_at(&inst_file))?;
+			// Note: `init_from_dir` always sends the instructions (see `upload_instructions`).
+			let (action, detail) =
+				match asst_obj.as_ref().map(|o| o.instructions.as_deref()) {
+					None => (Action::Create, None),
+					Some(Some(current)) if current == inst_content => {
+						(Action::Update, Some("same content".to_string()))
+					}
+					Some(_) => (Action::Update, Some("changed".to_string())),
+				};
+			plan.add(
+				action,
+				Target::Instructions,
+				&config.instructions_file,
+				detail,
+			);
+		}
+
+		// -- Conversation (see `load_or_create_conv_from_file`)
+		let conv_file = match conv_name {
+			Some(name) => dir
+				.join(".buddy")
+				.join("convs")
+				.join(format!("{name}.json")),
+			None => dir.join(".buddy").join("conv.json"),
+		};
+		let conv_name = conv_name.unwrap_or("default");
+		match load_json::<Conv>(&conv_file) {
+			Ok(conv) if recreate_conv => {
+				plan.add(
+					Action::Replace,
+					Target::Conversation,
+					conv_name,
+					Some(conv.thread_id.to_string()),
+				);
+			}
+			Ok(conv) => {
+				plan.add(
+					Action::Keep,
+					Target::Conversation,
+					conv_name,
+					Some(conv.thread_id.to_string()),
+				);
+			}
+			Err(_) => {
+				plan.add(Action::Create, Target::Conversation, conv_name, None)
+			}
+		}
+
+		// -- Files
+		if options.upload_files {
+			let asst_exists = asst_obj.is_some();
+			// Note: An empty id for the assistant not created yet (see `bundle_file_prefix`).
+			let asst_id = asst_obj.map(|o| o.id).unwrap_or_default();
+			let buddy = Buddy {
+				dir: dir.to_path_buf(),
+				ais_client,
+				asst_id: asst_id.into(),
+				config,
+				run_model: options.model,
+				event_bus,
+				upload_lock: Mutex::new(()),
+				usage_lock: std::sync::Mutex::new(()),
+			};
+			buddy.plan_files(&mut plan, asst_exists).await?;
+		}
+
+		Ok(plan)
+	}
 }
 
 /// Public functions
@@ -98,6 +395,13 @@ impl Buddy {
 		&self.config.name
 	}
 
+	/// The `.buddy/` data directory of this buddy (created if missing).
+	pub fn data_dir(&self) -> Result<PathBuf> {
+		let data_dir = self.dir.join(".buddy");
+		ensure_dir(&data_dir).map_err(Error::simple_fs_at(&data_dir))?;
+		Ok(data_dir)
+	}
+
 	pub fn subscribe(&self) -> Result<Receiver<Event>> {
 		self.event_bus.subscribe()
 	}
@@ -105,7 +409,8 @@ impl Buddy {
 	pub async fn upload_instructions(&self) -> Result<bool> {
 		let file = self.dir.join(&self.config.instructions_file);
 		if file.exists() {
-			let inst_content = read_to_string(&file)?;
+			let inst_content =
+				read_to_string(&file).map_err(Error::simple_fs_at(&file))?;
 			asst::upload_instructions(&self.ais_client, &self.asst_id, inst_content)
 				.await?;
 			self.event_bus.send(BuddyEvent::InstUploaded)?;
@@ -116,76 +421,290 @@ impl Buddy {
 	}
 
 	pub async fn upload_files(&self, recreate: bool) -> Result<u32> {
-		let mut num_uploaded = 0;
+		self.upload_files_impl(Reupload::from_recreate(recreate), None, false)
+			.await
+	}
 
-		// The .buddy/files
-		let data_files_dir = self.data_files_dir()?;
+	/// Same as `upload_files`, but the user already confirmed going over budget
+	/// (for the `BudgetPolicy::Confirm` policy).
+	pub async fn upload_files_confirmed(&self, recreate: bool) -> Result<u32> {
+		self.upload_files_impl(Reupload::from_recreate(recreate), None, true)
+			.await
+	}
 
-		// -- Clean the .buddy/files left over.
-		let exclude_element = format!("*{}*", &self.asst_id);
-		for file in list_files(
-			data_files_dir,
-			Some(&["*.rs", "*.md"]),
-			Some(&[&exclude_element]),
-		)? {
-			// Safeguard
-			if !file.to_str().contains(".buddy") {
-				return Err(Error::ShouldNotDeleteLocalFile(file.to_string()));
-			}
-			fs::remove_file(&file)?;
-		}
+	/// Watches the `instructions_file` and the `file_bundles` src_dir(s), and re-uploads
+	/// only the affected instructions or bundles on changes (see the watch `BuddyEvent`s).
+	///
+	/// Runs until an error occurs when starting, or the future is dropped.
+	/// A failed re-upload is sent as a `BuddyEvent::WatchUploadFailed` and the watch continues.
+	pub async fn watch(&self) -> Result<()> {
+		let (tx, mut rx) = mpsc::unbounded_channel();
 
-		// -- Generate and upload the .buddy/files bundle files.
+		// -- Start the watchers
+		let mut targets = vec![(
+			WatchTarget::Instructions,
+			self.dir.join(&self.config.instructions_file),
+		)];
 		for bundle in self.config.file_bundles.iter() {
 			let src_dir = self.dir.join(&bundle.src_dir);
-
 			if src_dir.is_dir() {
-				let src_globs: Vec<&str> =
-					bundle.src_globs.iter().map(AsRef::as_ref).collect();
-
-				let files = list_files(&src_dir, Some(&src_globs), None)?;
-
-				if !files.is_empty() {
-					// Compute bundle file name.
-					let bundle_file_name = format!(
-						"{}-{}-bundle-{}.{}",
-						self.name(),
-						bundle.bundle_name,
-						self.asst_id,
-						bundle.dst_ext
-					);
-					let bundle_file = self.data_files_dir()?.join(bundle_file_name);
-					// Note: Here bundle_file is an SPath because the file does not exist (SFile construction does an is_file() check by contract)
-					let bundle_file = SPath::from_path(bundle_file)?;
-
-					// If it does not exist, then we will force a reupload
-					let force_reupload = recreate || !bundle_file.path().exists();
-
-					// Rebundle no matter if exist or not (to check).
-					bundle_to_file(files, &bundle_file)?;
-
-					// Upload
-					let (_, uploaded) = asst::upload_file_by_name(
-						&self.ais_client,
-						&self.asst_id,
-						&bundle_file,
-						force_reupload,
-					)
-					.await?;
+				targets.push((
+					WatchTarget::Bundle(bundle.bundle_name.clone()),
+					src_dir,
+				));
+			}
+		}
+		// Note: The watcher threads stop when the handles are dropped (with this future).
+		let mut watchers = Vec::with_capacity(targets.len());
+		for (target, path) in targets.iter() {
+			watchers.push(spawn_watcher(target.clone(), path, tx.clone())?);
+		}
+		self.event_bus.send(BuddyEvent::WatchStarted {
+			paths: targets
+				.iter()
+				.map(|(_, path)| path.to_string_lossy().to_string())
+				.collect(),
+		})?;
 
-					if uploaded {
-						num_uploaded += 1;
+		// -- Re-upload the affected targets, once the changes are quiet
+		while let Some(target) = rx.recv().await {
+			let mut changed = vec![target];
+			let quiet = Duration::from_millis(WATCH_QUIET_MS);
+			while let Ok(Some(target)) = timeout(quiet, rx.recv()).await {
+				if !changed.contains(&target) {
+					changed.push(target);
+				}
+			}
+
+			self.event_bus.send(BuddyEvent::WatchChanged {
+				targets: changed.iter().map(|t| t.name().to_string()).collect(),
+			})?;
+
+			for target in changed {
+				// Note: No one can confirm, so with the `Confirm` budget policy,
+				//       the upload is skipped.
+				let res = match &target {
+					WatchTarget::Instructions => {
+						self.upload_instructions().await.map(|_| ())
 					}
+					WatchTarget::Bundle(bundle_name) => {
+						let res = self
+							.upload_files_impl(
+								Reupload::Changed,
+								Some(bundle_name),
+								false,
+							)
+							.await;
+						self.skip_unconfirmed_upload(res).and_then(|num_uploaded| {
+							if num_uploaded == Some(0) {
+								self.event_bus.send(BuddyEvent::BundleUpToDate {
+									bundle_name: bundle_name.clone(),
+								})?;
+							}
+							Ok(())
+						})
+					}
+				};
+				if let Err(err) = res {
+					self.event_bus.send(BuddyEvent::WatchUploadFailed {
+						target: target.name().to_string(),
+						cause: err.to_string(),
+					})?;
 				}
 			}
 		}
 
-		Ok(num_uploaded)
+		Ok(())
 	}
 
+	/// Loads or creates the default conversation (`.buddy/conv.json`).
 	pub async fn load_or_create_conv(&self, recreate: bool) -> Result<Conv> {
 		let conv_file = self.data_dir()?.join("conv.json");
+		self.load_or_create_conv_from_file(conv_file, recreate)
+			.await
+	}
+
+	/// Loads or creates a named conversation (`.buddy/convs/{name}.json`).
+	pub async fn load_or_create_named_conv(
+		&self,
+		name: &str,
+		recreate: bool,
+	) -> Result<Conv> {
+		let is_valid = !name.is_empty()
+			&& name
+				.chars()
+				.all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+		if !is_valid {
+			return Err(Error::ConvNameInvalid(name.to_string()));
+		}
+
+		let convs_dir = self.data_dir()?.join("convs");
+		ensure_dir(&convs_dir).map_err(Error::simple_fs_at(&convs_dir))?;
+		let conv_file = convs_dir.join(format!("{name}.json"));
+		self.load_or_create_conv_from_file(conv_file, recreate)
+			.await
+	}
+
+	/// Adds a note to the conversation thread, without running the assistant
+	/// (e.g., to tell it that the instructions or files were refreshed).
+	pub async fn add_conv_note(&self, conv: &Conv, note: &str) -> Result<()> {
+		asst::add_thread_msg(&self.ais_client, &conv.thread_id, note).await?;
+		self.event_bus.send(BuddyEvent::ConvNoteAdded)?;
+		Ok(())
+	}
+
+	pub async fn chat(&self, conv: &Conv, msg: &str) -> Result<ChatResponse> {
+		self.chat_impl(conv, msg, &[], false).await
+	}
+
+	/// Same as `chat`, but the user already confirmed going over budget
+	/// (for the `BudgetPolicy::Confirm` policy).
+	pub async fn chat_confirmed(
+		&self,
+		conv: &Conv,
+		msg: &str,
+	) -> Result<ChatResponse> {
+		self.chat_impl(conv, msg, &[], true).await
+	}
+
+	/// Sends a message with local files attached to this message only
+	/// (uploaded or inlined, per the `[attach]` mode of the `buddy.toml`).
+	///
+	/// Note: The uploaded files are deleted after the run.
+	pub async fn chat_with_files(
+		&self,
+		conv: &Conv,
+		msg: &str,
+		files: &[PathBuf],
+	) -> Result<ChatResponse> {
+		self.chat_impl(conv, msg, files, false).await
+	}
+
+	/// Same as `chat_with_files`, but the user already confirmed going over budget.
+	pub async fn chat_with_files_confirmed(
+		&self,
+		conv: &Conv,
+		msg: &str,
+		files: &[PathBuf],
+	) -> Result<ChatResponse> {
+		self.chat_impl(conv, msg, files, true).await
+	}
+
+	/// Parses a unified diff, and checks that each hunk applies cleanly to the files
+	/// of the `file_bundles` src_dir(s).
+	///
+	/// Returns one change per file (the patches of the same file are merged).
+	/// Nothing is written, see `apply_changes`.
+	pub fn check_patch(&self, patch_text: &str) -> Result<Vec<FileChange>> {
+		let mut changes: Vec<FileChange> = Vec::new();
+
+		for file_patch in parse_patch(patch_text)? {
+			let Some(new_path) = file_patch.new_path.as_deref() else {
+				return Err(Error::PatchFileDeletionNotSupported(
+					file_patch.path().to_string(),
+				));
+			};
+			let is_new = file_patch.old_path.is_none();
+			let resolved = resolve_patch_path(
+				&self.dir,
+				&self.config.file_bundles,
+				new_path,
+				is_new,
+			)?;
+
+			// The patches of the same file apply in sequence, to the accumulated content.
+			if let Some(change) =
+				changes.iter_mut().find(|c| c.path == resolved.path)
+			{
+				change.new_content = apply_patch(&change.new_content, &file_patch)?;
+				continue;
+			}
+
+			let old_content = if is_new {
+				None
+			} else {
+				let content = read_to_string(&resolved.path)
+					.map_err(Error::simple_fs_at(&resolved.path))?;
+				Some(content)
+			};
+			let new_content = apply_patch(
+				old_content.as_deref().unwrap_or_default(),
+				&file_patch,
+			)?;
+
+			changes.push(FileChange {
+				path: resolved.path,
+				bundle_name: resolved.bundle.bundle_name.clone(),
+				rel_path: resolved.rel_path,
+				old_content,
+				new_content,
+			});
+		}
+
+		Ok(changes)
+	}
+
+	/// Writes the checked changes, after a backup of the current files
+	/// in `.buddy/backups/{timestamp}/{bundle_name}/`.
+	///
+	/// Returns the backup directory.
+	pub fn apply_changes(&self, changes: &[FileChange]) -> Result<PathBuf> {
+		// -- Make sure the files did not change since the check.
+		for change in changes {
+			let current = if change.path.is_file() {
+				Some(read_to_string(&change.path)?)
+			} else {
+				None
+			};
+			if current != change.old_content {
+				return Err(Error::PatchFileChanged(
+					change.path.to_string_lossy().to_string(),
+				));
+			}
+		}
+
+		// -- Backup the current files.
+		let timestamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+		let backup_dir = self.data_dir()?.join("backups").join(timestamp);
+		for change in changes.iter().filter(|c| c.old_content.is_some()) {
+			let backup_file =
+				backup_dir.join(&change.bundle_name).join(&change.rel_path);
+			if let Some(parent) = backup_file.parent() {
+				ensure_dir(parent).map_err(Error::simple_fs_at(parent))?;
+			}
+			fs::copy(&change.path, &backup_file)?;
+		}
 
+		// -- Write the new contents.
+		for change in changes {
+			fs::write(&change.path, &change.new_content)?;
+		}
+
+		Ok(backup_dir)
+	}
+
+	/// Returns the usage (tokens and cost) for the conversation, today, and in total.
+	pub fn usage_report(&self, conv: &Conv) -> Result<UsageReport> {
+		let store = UsageStore::load(&self.usage_file()?)?;
+
+		Ok(UsageReport {
+			model: self.model().to_string(),
+			priced: self.price().is_some(),
+			conv: store.conv(&conv.thread_id),
+			uploads: store.uploads.clone(),
+			today: store.today(),
+			total: store.total,
+		})
+	}
+}
+
+/// Private functions
+impl Buddy {
+	async fn load_or_create_conv_from_file(
+		&self,
+		conv_file: PathBuf,
+		recreate: bool,
+	) -> Result<Conv> {
 		if recreate && conv_file.exists() {
 			fs::remove_file(&conv_file)?;
 		}
@@ -200,37 +719,708 @@ impl Buddy {
 			let thread_id = asst::create_thread(&self.ais_client).await?;
 			self.event_bus.send(BuddyEvent::ConvCreated)?;
 			let conv = thread_id.into();
-			save_json(&conv_file, &conv)?;
+			save_json(&conv_file, &conv).map_err(Error::simple_fs_at(&conv_file))?;
 			conv
 		};
 
 		Ok(conv)
 	}
 
-	pub async fn chat(&self, conv: &Conv, msg: &str) -> Result<String> {
-		let res = asst::run_thread_msg(
+	async fn chat_impl(
+		&self,
+		conv: &Conv,
+		msg: &str,
+		files: &[PathBuf],
+		confirmed: bool,
+	) -> Result<ChatResponse> {
+		// -- Load the attachments
+		let mode = self.config.attach.mode;
+		let mut attachments = load_attachments(
+			files,
+			&self.config.attach,
+			mode == AttachMode::Upload,
+		)?;
+		self.redact_attachments(&mut attachments)?;
+		let inlined_msg;
+		let msg = match mode {
+			AttachMode::Inline if !attachments.is_empty() => {
+				inlined_msg = inline_msg(msg, &attachments);
+				inlined_msg.as_str()
+			}
+			_ => msg,
+		};
+
+		// -- Check the budget
+		// Note: The uploaded files are estimated as if all their content was in the prompt.
+		let upload_tokens: u64 = match mode {
+			AttachMode::Upload => attachments.iter().map(|a| a.tokens()).sum(),
+			AttachMode::Inline => 0,
+		};
+		let prompt_tokens = self.prompt_tokens(conv, msg)? + upload_tokens;
+		let estimate = Estimate {
+			tokens: prompt_tokens,
+			cost_usd: self
+				.price()
+				.map(|p| p.cost(prompt_tokens, 0))
+				.unwrap_or_default(),
+		};
+		self.check_budget(estimate, true, confirmed)?;
+
+		if !attachments.is_empty() {
+			self.event_bus.send(BuddyEvent::FilesAttached {
+				mode,
+				files: attachments
+					.iter()
+					.map(|a| a.path.to_string_lossy().to_string())
+					.collect(),
+			})?;
+		}
+
+		// -- Upload the attachments
+		let uploaded = match mode {
+			AttachMode::Upload => self.upload_attachments(&attachments).await?,
+			AttachMode::Inline => Vec::new(),
+		};
+		let file_ids: Vec<FileId> = uploaded.iter().map(|f| f.id.clone()).collect();
+
+		// -- Run the message
+		let run_res = asst::run_thread_msg(
 			&self.ais_client,
 			&self.asst_id,
 			&conv.thread_id,
 			msg,
+			&file_ids,
+			self.run_model.as_deref(),
 		)
-		.await?;
+		.await;
+
+		// -- Clean up the uploaded attachments (even when the run failed)
+		for file_ref in uploaded {
+			asst::delete_org_file(&self.ais_client, file_ref).await?;
+		}
+		let mut res = run_res?;
+
+		res.usage = self.record_usage(conv, msg, upload_tokens, &res.text)?;
 
 		Ok(res)
 	}
-}
 
-/// Private functions
-impl Buddy {
-	fn data_dir(&self) -> Result<PathBuf> {
-		let data_dir = self.dir.join(".buddy");
-		ensure_dir(&data_dir)?;
-		Ok(data_dir)
+	/// Bundles and uploads the `file_bundles` (only `bundle_name` when given).
+	async fn upload_files_impl(
+		&self,
+		reupload: Reupload,
+		bundle_name: Option<&str>,
+		confirmed: bool,
+	) -> Result<u32> {
+		let _upload_guard = self.upload_lock.lock().await;
+		let mut num_uploaded = 0;
+
+		// The .buddy/files
+		let data_files_dir = self.data_files_dir()?;
+
+		// -- Generate the bundle files content.
+		let bundling = self.generate_bundles(&data_files_dir, bundle_name)?;
+
+		// Note: The checks are done before any change, local or remote.
+		if self.config.redact.policy == RedactPolicy::Block
+			&& !bundling.secret_files.is_empty()
+		{
+			return Err(Error::SecretsFound(bundling.secret_files));
+		}
+
+		// -- The bundle files to upload.
+		let remote_files =
+			asst::get_files_hashmap(&self.ais_client, &self.asst_id).await?;
+		let to_upload: Vec<&BundleFile> = bundling
+			.files
+			.iter()
+			.filter(|f| {
+				f.force_reupload(reupload)
+					|| !remote_files.contains_key(f.file.file_name())
+			})
+			.collect();
+
+		// -- Check the budget for the bundles that will be uploaded.
+		if self.config.budget.is_some() && !to_upload.is_empty() {
+			let tokens = to_upload.iter().map(|f| approx_tokens(&f.content)).sum();
+			let estimate = Estimate {
+				tokens,
+				cost_usd: self
+					.price()
+					.map(|p| p.cost(tokens, 0))
+					.unwrap_or_default(),
+			};
+			self.check_budget(estimate, false, confirmed)?;
+		}
+
+		// -- Clean the .buddy/files left over.
+		for file in self.leftover_bundle_files(&data_files_dir)? {
+			// Safeguard
+			if !file.to_str().contains(".buddy") {
+				return Err(Error::ShouldNotDeleteLocalFile(file.to_string()));
+			}
+			fs::remove_file(&file)?;
+		}
+
+		// -- Remove the part files not generated anymore (e.g., the bundle shrank).
+		self.remove_stale_bundle_files(|file_name| bundling.is_stale(file_name))
+			.await?;
+
+		// -- Upload the bundle files.
+		// Note: A bundle file is uploaded from `.buddy/upload/`, and moved to `.buddy/files/`
+		//       only once uploaded, so that `.buddy/files/` has the uploaded contents
+		//       (see `Reupload::Changed`), even after a failed upload.
+		let upload_dir = self.data_dir()?.join("upload");
+		ensure_dir(&upload_dir).map_err(Error::simple_fs_at(&upload_dir))?;
+		for bundle_file in to_upload {
+			let upload_file = upload_dir.join(bundle_file.file.file_name());
+			fs::write(&upload_file, &bundle_file.content)?;
+			let upload_file = SPath::from_path(upload_file)?;
+
+			let res = asst::upload_file_by_name(
+				&self.ais_client,
+				&self.asst_id,
+				&upload_file,
+				bundle_file.force_reupload(reupload),
+			)
+			.await;
+			let (file_id, uploaded) = match res {
+				Ok(res) => res,
+				Err(err) => {
+					fs::remove_file(&upload_file)?;
+					return Err(err);
+				}
+			};
+
+			if uploaded {
+				fs::rename(&upload_file, &bundle_file.file)?;
+				num_uploaded += 1;
+				self.record_upload_usage(&bundle_file.content)?;
+				self.audit_upload(
+					Some(bundle_file.bundle_name.clone()),
+					&bundle_file.file,
+					&file_id,
+					bundle_file.sources.clone(),
+				)?;
+			} else {
+				fs::remove_file(&upload_file)?;
+			}
+		}
+
+		Ok(num_uploaded)
+	}
+
+	/// Adds to the plan the file changes of the initial `upload_files_impl` (see
+	/// `init_from_dir`), with `asst_exists` false when the assistant is (re)created.
+	async fn plan_files(&self, plan: &mut Plan, asst_exists: bool) -> Result<()> {
+		// Note: Not `data_files_dir()`, which creates the directory.
+		let files_dir = self.dir.join(".buddy").join("files");
+
+		// -- The .buddy/files left over
+		for file in self.leftover_bundle_files(&files_dir)? {
+			let detail = "left over by a previous assistant".to_string();
+			plan.add(
+				Action::Delete,
+				Target::LocalFile,
+				file.file_name(),
+				Some(detail),
+			);
+		}
+
+		// -- The bundle files
+		let bundling = self.generate_bundles(&files_dir, None)?;
+		if self.config.redact.policy == RedactPolicy::Block {
+			plan.blocked_by_secrets = bundling.secret_files.clone();
+		}
+
+		let remote_files = if asst_exists {
+			asst::get_files_hashmap(&self.ais_client, &self.asst_id).await?
+		} else {
+			HashMap::new()
+		};
+
+		for bundle_file in bundling.files.iter() {
+			let file_name = bundle_file.file.file_name();
+			let detail = format!(
+				"{}, {} files, {} KB",
+				bundle_file.bundle_name,
+				bundle_file.sources.len(),
+				bundle_file.content.len().div_ceil(1024)
+			);
+			let changed =
+				bundle_file.previous.as_deref() != Some(&bundle_file.content);
+			let (action, detail) = if !asst_exists {
+				(
+					Action::Create,
+					format!("{detail}, named with the new assistant id"),
+				)
+			} else if !remote_files.contains_key(file_name) {
+				(Action::Create, detail)
+			} else if bundle_file.force_reupload(Reupload::Missing) {
+				(Action::Replace, format!("{detail}, no local copy"))
+			} else if changed {
+				(
+					Action::Keep,
+					format!("{detail}, changed but not re-uploaded (see /rf)"),
+				)
+			} else {
+				(Action::Keep, detail)
+			};
+			plan.add(action, Target::RemoteFile, file_name, Some(detail));
+		}
+
+		// -- The stale bundle parts (see `remove_stale_bundle_files`)
+		if files_dir.is_dir() {
+			let mut file_names: Vec<String> = Vec::new();
+			for entry in fs::read_dir(&files_dir)? {
+				let path = entry?.path();
+				let Some(file_name) = path.file_name().and_then(|n| n.to_str())
+				else {
+					continue;
+				};
+				if path.is_file() && bundling.is_stale(file_name) {
+					file_names.push(file_name.to_string());
+				}
+			}
+			file_names.sort();
+			for file_name in file_names {
+				let detail = "stale bundle part".to_string();
+				plan.add(Action::Delete, Target::LocalFile, file_name, Some(detail));
+			}
+		}
+
+		let mut file_names: Vec<&String> = remote_files
+			.keys()
+			.filter(|file_name| bundling.is_stale(file_name))
+			.collect();
+		file_names.sort();
+		for file_name in file_names {
+			let detail = "stale bundle part".to_string();
+			plan.add(Action::Delete, Target::RemoteFile, file_name, Some(detail));
+		}
+
+		Ok(())
+	}
+
+	/// The local bundle files left over by a previous assistant (without its id).
+	fn leftover_bundle_files(&self, files_dir: &Path) -> Result<Vec<SFile>> {
+		if !files_dir.is_dir() {
+			return Ok(Vec::new());
+		}
+		// Note: All of them for an assistant not created yet (empty id).
+		let exclude_element = format!("*{}*", &self.asst_id);
+		let exclude_globs = [exclude_element.as_str()];
+		let exclude_globs = (!self.asst_id.is_empty()).then_some(&exclude_globs[..]);
+		let files = list_files(files_dir, Some(&["*.rs", "*.md"]), exclude_globs)?;
+		Ok(files)
+	}
+
+	/// Generates the bundle files content of the `file_bundles` (only `bundle_name`
+	/// when given), in `files_dir`.
+	///
+	/// Note: Nothing is written, but the skipped files, masked secrets, and splits
+	///       are sent as events.
+	fn generate_bundles(
+		&self,
+		files_dir: &Path,
+		bundle_name: Option<&str>,
+	) -> Result<Bundling> {
+		let mut bundling = Bundling::default();
+		let redact = &self.config.redact;
+		let redactor = if redact.enabled {
+			Some(Redactor::new(&redact.patterns)?)
+		} else {
+			None
+		};
+
+		let bundles = self
+			.config
+			.file_bundles
+			.iter()
+			.filter(|b| bundle_name.is_none_or(|name| b.bundle_name == name));
+		for bundle in bundles {
+			bundling.prefixes.push(self.bundle_file_prefix(bundle));
+
+			let src_dir = self.dir.join(&bundle.src_dir);
+			if !src_dir.is_dir() {
+				continue;
+			}
+
+			let files = list_bundle_files(&src_dir, bundle)?;
+			let (mut sources, skipped) = read_sources(&src_dir, files, bundle)?;
+			for Skipped { file, reason } in skipped {
+				self.event_bus.send(BuddyEvent::BundleFileSkipped {
+					bundle_name: bundle.bundle_name.clone(),
+					path: file.to_string(),
+					reason,
+				})?;
+			}
+
+			if let Some(redactor) = redactor.as_ref() {
+				for source in sources.iter_mut() {
+					let count = redactor.redact(&mut source.content);
+					if count > 0 {
+						self.event_bus.send(BuddyEvent::SecretsRedacted {
+							bundle_name: bundle.bundle_name.clone(),
+							path: source.rel_path.clone(),
+							count,
+						})?;
+						bundling.secret_files.push(format!(
+							"{}/{}",
+							bundle.bundle_name, source.rel_path
+						));
+					}
+				}
+			}
+
+			if sources.is_empty() {
+				continue;
+			}
+
+			let parts = bundle_parts(&sources, bundle);
+			if parts.len() > 1 {
+				self.event_bus.send(BuddyEvent::BundleSplit {
+					bundle_name: bundle.bundle_name.clone(),
+					parts: parts.len(),
+				})?;
+			}
+
+			let split = parts.len() > 1;
+			for (idx, part) in parts.into_iter().enumerate() {
+				let part_num = split.then_some(idx + 1);
+				let bundle_file =
+					files_dir.join(self.bundle_file_name(bundle, part_num));
+				// Note: Here bundle_file is an SPath because the file might not exist (SFile construction does an is_file() check by contract)
+				let bundle_file = SPath::from_path(bundle_file)?;
+				let previous = read_to_string(&bundle_file).ok();
+
+				let sources = part
+					.sources
+					.iter()
+					.map(|source| AuditSource {
+						path: format!(
+							"{}/{}",
+							bundle.src_dir.trim_end_matches('/'),
+							source.rel_path
+						),
+						sha256: source.sha256.clone(),
+					})
+					.collect();
+				bundling.files.push(BundleFile {
+					file: bundle_file,
+					content: part.content,
+					previous,
+					bundle_name: bundle.bundle_name.clone(),
+					sources,
+				});
+			}
+		}
+
+		Ok(bundling)
+	}
+
+	/// The bundle file name, with `-part{num}` when the bundle is split.
+	fn bundle_file_name(
+		&self,
+		bundle: &FileBundle,
+		part_num: Option<usize>,
+	) -> String {
+		let part = part_num
+			.map(|num| format!("-part{num}"))
+			.unwrap_or_default();
+		format!(
+			"{}{part}.{}",
+			self.bundle_file_prefix(bundle),
+			bundle.dst_ext
+		)
+	}
+
+	/// The bundle file name prefix, without the assistant id for the plan of an
+	/// assistant not created yet (empty id).
+	fn bundle_file_prefix(&self, bundle: &FileBundle) -> String {
+		let prefix = format!("{}-{}-bundle", self.name(), bundle.bundle_name);
+		if self.asst_id.is_empty() {
+			prefix
+		} else {
+			format!("{prefix}-{}", self.asst_id)
+		}
+	}
+
+	/// Removes the local and uploaded bundle files matching `is_stale`.
+	async fn remove_stale_bundle_files(
+		&self,
+		is_stale: impl Fn(&str) -> bool,
+	) -> Result<()> {
+		let mut removed: Vec<String> = Vec::new();
+
+		// -- Local files
+		for entry in fs::read_dir(self.data_files_dir()?)? {
+			let path = entry?.path();
+			let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+				continue;
+			};
+			if path.is_file() && is_stale(file_name) {
+				fs::remove_file(&path)?;
+				removed.push(file_name.to_string());
+			}
+		}
+
+		// -- Uploaded files
+		let remote_files =
+			asst::get_files_hashmap(&self.ais_client, &self.asst_id).await?;
+		for (file_name, file_id) in remote_files {
+			if is_stale(&file_name) {
+				let file_ref = FileRef::new(file_name.as_str(), file_id);
+				asst::delete_asst_file(&self.ais_client, &self.asst_id, file_ref)
+					.await?;
+				if !removed.contains(&file_name) {
+					removed.push(file_name);
+				}
+			}
+		}
+
+		for file_name in removed {
+			self.event_bus
+				.send(BuddyEvent::BundlePartRemoved { file_name })?;
+		}
+
+		Ok(())
 	}
 
 	fn data_files_dir(&self) -> Result<PathBuf> {
 		let dir = self.data_dir()?.join("files");
-		ensure_dir(&dir)?;
+		ensure_dir(&dir).map_err(Error::simple_fs_at(&dir))?;
 		Ok(dir)
 	}
+
+	/// Uploads the attachments to the account (not to the assistant).
+	///
+	/// On failure, the files already uploaded are deleted.
+	async fn upload_attachments(
+		&self,
+		attachments: &[Attachment],
+	) -> Result<Vec<FileRef>> {
+		let tmp_dir = self.data_dir()?.join("attach");
+		ensure_dir(&tmp_dir).map_err(Error::simple_fs_at(&tmp_dir))?;
+
+		let mut uploaded = Vec::with_capacity(attachments.len());
+		for attachment in attachments {
+			let res = match attachment.upload_file(&tmp_dir) {
+				Ok((file, is_tmp)) => {
+					let res = asst::upload_org_file(&self.ais_client, &file)
+						.await
+						.and_then(|file_id| {
+							let source = AuditSource {
+								path: attachment.path.to_string_lossy().to_string(),
+								sha256: sha256_hex(&fs::read(&attachment.path)?),
+							};
+							self.audit_upload(None, &file, &file_id, vec![source])?;
+							Ok(file_id)
+						});
+					if is_tmp {
+						fs::remove_file(&file)?;
+					}
+					res.map(|file_id| FileRef::new(&file, file_id))
+				}
+				Err(err) => Err(err),
+			};
+
+			match res {
+				Ok(file_ref) => uploaded.push(file_ref),
+				Err(err) => {
+					for file_ref in uploaded {
+						asst::delete_org_file(&self.ais_client, file_ref).await?;
+					}
+					return Err(err);
+				}
+			}
+		}
+
+		Ok(uploaded)
+	}
+
+	/// Masks the secrets of the attachments, per the `[redact]` config (like the bundles).
+	fn redact_attachments(&self, attachments: &mut [Attachment]) -> Result<()> {
+		let redact = &self.config.redact;
+		if !redact.enabled {
+			return Ok(());
+		}
+		let redactor = Redactor::new(&redact.patterns)?;
+
+		let mut secret_files = Vec::new();
+		for attachment in attachments.iter_mut() {
+			let count = attachment.redact(&redactor);
+			if count > 0 {
+				let path = attachment.path.to_string_lossy().to_string();
+				self.event_bus.send(BuddyEvent::AttachSecretsRedacted {
+					path: path.clone(),
+					count,
+				})?;
+				secret_files.push(path);
+			}
+		}
+
+		if redact.policy == RedactPolicy::Block && !secret_files.is_empty() {
+			return Err(Error::SecretsFound(secret_files));
+		}
+
+		Ok(())
+	}
+
+	/// Appends an upload to the `.buddy/audit.jsonl` log.
+	fn audit_upload(
+		&self,
+		bundle_name: Option<String>,
+		file: &SPath,
+		file_id: &FileId,
+		sources: Vec<AuditSource>,
+	) -> Result<()> {
+		let content = fs::read(file)?;
+		let entry = AuditEntry {
+			timestamp: Local::now().to_rfc3339(),
+			asst_id: self.asst_id.to_string(),
+			bundle_name,
+			file_name: file.file_name().to_string(),
+			file_id: file_id.to_string(),
+			size: content.len() as u64,
+			sha256: sha256_hex(&content),
+			sources,
+		};
+		append_audit(&self.data_dir()?.join("audit.jsonl"), &entry)
+	}
+
+	fn usage_file(&self) -> Result<PathBuf> {
+		Ok(self.data_dir()?.join("usage.json"))
+	}
+
+	/// Returns `None` when the upload needs a confirmation (`BudgetPolicy::Confirm`),
+	/// after sending a `BuddyEvent::BudgetUploadSkipped`.
+	fn skip_unconfirmed_upload(&self, res: Result<u32>) -> Result<Option<u32>> {
+		match res {
+			Err(Error::BudgetConfirmRequired(exceeded)) => {
+				self.event_bus
+					.send(BuddyEvent::BudgetUploadSkipped(exceeded))?;
+				Ok(None)
+			}
+			other => other.map(Some),
+		}
+	}
+
+	/// Loads the `.buddy/usage.json` store, updates it with `update`, and saves it,
+	/// one update at a time.
+	fn update_usage<T>(
+		&self,
+		update: impl FnOnce(&mut UsageStore) -> Result<T>,
+	) -> Result<T> {
+		// Note: The lock only guards the file, so a poisoned lock is still usable.
+		let _usage_guard = self
+			.usage_lock
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+
+		let usage_file = self.usage_file()?;
+		let mut store = UsageStore::load(&usage_file)?;
+		let res = update(&mut store)?;
+		store.save(&usage_file)?;
+
+		Ok(res)
+	}
+
+	/// Estimates the tokens of an exchange and records them in the `.buddy/usage.json`.
+	///
+	/// The prompt of a run is the instructions, the thread so far, the new message,
+	/// and the `attach_tokens` of the uploaded attachments (estimated as if in the prompt).
+	fn record_usage(
+		&self,
+		conv: &Conv,
+		msg: &str,
+		attach_tokens: u64,
+		res: &str,
+	) -> Result<Usage> {
+		let instructions_tokens = self.instructions_tokens()?;
+		self.update_usage(|store| {
+			let msg_tokens = approx_tokens(msg);
+			let completion_tokens = approx_tokens(res);
+			let prompt_tokens = instructions_tokens
+				+ store.thread_tokens(&conv.thread_id)
+				+ msg_tokens + attach_tokens;
+
+			let cost_usd = self
+				.price()
+				.map(|p| p.cost(prompt_tokens, completion_tokens))
+				.unwrap_or_default();
+			let usage = Usage::new(prompt_tokens, completion_tokens, cost_usd);
+
+			// Note: The attachments are deleted after the run, so not in the thread.
+			store.record(&conv.thread_id, &usage, msg_tokens + completion_tokens);
+			Ok(usage)
+		})
+	}
+
+	/// Records an uploaded bundle file in the `.buddy/usage.json`, with the same
+	/// estimate as the budget check of `upload_files_impl`.
+	fn record_upload_usage(&self, content: &str) -> Result<()> {
+		let tokens = approx_tokens(content);
+		let cost_usd = self.price().map(|p| p.cost(tokens, 0)).unwrap_or_default();
+		self.update_usage(|store| {
+			store.record_upload(&Usage::new(tokens, 0, cost_usd));
+			Ok(())
+		})
+	}
+
+	/// Estimates the prompt tokens of a run for a new message (before it is sent).
+	fn prompt_tokens(&self, conv: &Conv, msg: &str) -> Result<u64> {
+		let store = UsageStore::load(&self.usage_file()?)?;
+		Ok(self.instructions_tokens()?
+			+ store.thread_tokens(&conv.thread_id)
+			+ approx_tokens(msg))
+	}
+
+	/// The model of the runs (the `--model` override, or the `buddy.toml` one).
+	fn model(&self) -> &str {
+		self.run_model.as_deref().unwrap_or(&self.config.model)
+	}
+
+	fn price(&self) -> Option<&ModelPrice> {
+		self.config.prices.get(self.model())
+	}
+
+	/// Applies the `[budget]` policy for a pending request.
+	fn check_budget(
+		&self,
+		estimate: Estimate,
+		is_msg: bool,
+		confirmed: bool,
+	) -> Result<()> {
+		let Some(budget) = self.config.budget.as_ref() else {
+			return Ok(());
+		};
+
+		let store = UsageStore::load(&self.usage_file()?)?;
+		let Some(exceeded) = budget::check(budget, &store, estimate, is_msg) else {
+			return Ok(());
+		};
+
+		match budget.policy {
+			BudgetPolicy::Block => Err(Error::BudgetExceeded(exceeded)),
+			BudgetPolicy::Confirm if !confirmed => {
+				Err(Error::BudgetConfirmRequired(exceeded))
+			}
+			BudgetPolicy::Warn | BudgetPolicy::Confirm => {
+				self.event_bus.send(BuddyEvent::BudgetWarning(exceeded))?;
+				Ok(())
+			}
+		}
+	}
+
+	fn instructions_tokens(&self) -> Result<u64> {
+		let file = self.dir.join(&self.config.instructions_file);
+		if file.exists() {
+			let content =
+				read_to_string(&file).map_err(Error::simple_fs_at(&file))?;
+			Ok(approx_tokens(&content))
+		} else {
+			Ok(0)
+		}
+	}
 }
@@ -0,0 +1,104 @@
+//! The dry run of `Buddy::init_from_dir` (see `Buddy::plan`).
+
+use serde::Serialize;
+
+// region:    --- Types
+
+/// What the buddy initialization would change, without any change made.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+	pub buddy_name: String,
+	/// The id of the assistant kept, `None` when it is created (or replaced), and the
+	/// remote file names do not have the new id yet.
+	pub asst_id: Option<String>,
+	pub changes: Vec<PlanChange>,
+	/// The bundled files with secrets, when the `block` redact policy would refuse
+	/// the upload (`{bundle_name}/{rel_path}`).
+	pub blocked_by_secrets: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanChange {
+	pub action: PlanAction,
+	pub target: PlanTarget,
+	/// The assistant name, the instructions file, the conversation name, or the
+	/// bundle file name.
+	pub name: String,
+	pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanAction {
+	Create,
+	Update,
+	/// Deleted, then created again.
+	Replace,
+	Delete,
+	/// Nothing to do.
+	Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanTarget {
+	Assistant,
+	Instructions,
+	/// The conversation (thread) of the session.
+	Conversation,
+	/// A bundle file uploaded to the assistant.
+	RemoteFile,
+	/// A bundle file in `.buddy/files/`.
+	LocalFile,
+}
+
+// endregion: --- Types
+
+impl Plan {
+	pub(super) fn new(buddy_name: impl Into<String>) -> Self {
+		Plan {
+			buddy_name: buddy_name.into(),
+			asst_id: None,
+			changes: Vec::new(),
+			blocked_by_secrets: Vec::new(),
+		}
+	}
+
+	pub(super) fn add(
+		&mut self,
+		action: PlanAction,
+		target: PlanTarget,
+		name: impl Into<String>,
+		detail: Option<String>,
+	) {
+		self.changes.push(PlanChange {
+			action,
+			target,
+			name: name.into(),
+			detail,
+		});
+	}
+
+	/// Returns the `(to_add, to_change, to_destroy)` counts, a replace counting as
+	/// one add and one destroy.
+	pub fn counts(&self) -> (usize, usize, usize) {
+		let mut counts = (0, 0, 0);
+		for change in self.changes.iter() {
+			match change.action {
+				PlanAction::Create => counts.0 += 1,
+				PlanAction::Update => counts.1 += 1,
+				PlanAction::Replace => {
+					counts.0 += 1;
+					counts.2 += 1;
+				}
+				PlanAction::Delete => counts.2 += 1,
+				PlanAction::Keep => (),
+			}
+		}
+		counts
+	}
+
+	pub fn has_changes(&self) -> bool {
+		self.changes.iter().any(|c| c.action != PlanAction::Keep)
+	}
+}
@@ -0,0 +1,174 @@
+//! Token usage and cost accounting, persisted in `.buddy/usage.json`.
+//!
+//! Notes:
+//! - Tokens are estimated locally (see `utils::tokens`), so costs are approximations.
+//! - The prompt side of a run includes the instructions and the whole thread so far,
+//!   which is why each conversation keeps track of its accumulated `thread_tokens`.
+//! - The bundle uploads are recorded as prompt tokens (like the budget estimates),
+//!   outside of the conversations.
+
+use crate::ais::Usage;
+use crate::{Error, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use simple_fs::{load_json, save_json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// region:    --- UsageReport
+
+/// Usage snapshot returned by `Buddy::usage_report`.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+	pub model: String,
+	/// `false` when the model has no entry in the `buddy.toml` price table.
+	pub priced: bool,
+	pub conv: Usage,
+	/// The bundle uploads (all days).
+	pub uploads: Usage,
+	pub today: Usage,
+	pub total: Usage,
+}
+
+// endregion: --- UsageReport
+
+// region:    --- UsageStore
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct ConvUsage {
+	#[serde(flatten)]
+	pub usage: Usage,
+	/// Accumulated tokens of the thread messages (user and assistant).
+	pub thread_tokens: u64,
+}
+
+/// The `.buddy/usage.json` content.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct UsageStore {
+	pub total: Usage,
+	/// Usage by conversation (thread id).
+	pub convs: BTreeMap<String, ConvUsage>,
+	/// Usage of the bundle uploads.
+	#[serde(default)]
+	pub uploads: Usage,
+	/// Usage by local day (`YYYY-MM-DD`).
+	pub days: BTreeMap<String, Usage>,
+}
+
+impl UsageStore {
+	/// Loads the store, or returns an empty one if the file does not exist yet.
+	pub fn load(file: &Path) -> Result<Self> {
+		if file.exists() {
+			Ok(load_json(file).map_err(Error::simple_fs_at(file))?)
+		} else {
+			Ok(UsageStore::default())
+		}
+	}
+
+	/// Saves the store to a temp file, then renames it, so that the file is never
+	/// partially written.
+	pub fn save(&self, file: &Path) -> Result<()> {
+		let tmp_file = file.with_extension("json.tmp");
+		save_json(&tmp_file, self).map_err(Error::simple_fs_at(&tmp_file))?;
+		fs::rename(&tmp_file, file)?;
+		Ok(())
+	}
+
+	pub fn thread_tokens(&self, conv_key: &str) -> u64 {
+		self.convs
+			.get(conv_key)
+			.map(|c| c.thread_tokens)
+			.unwrap_or_default()
+	}
+
+	/// Records a usage for a conversation, with the tokens the exchange added to the thread.
+	pub fn record(
+		&mut self,
+		conv_key: &str,
+		usage: &Usage,
+		added_thread_tokens: u64,
+	) {
+		self.total += usage;
+
+		let conv = self.convs.entry(conv_key.to_string()).or_default();
+		conv.usage += usage;
+		conv.thread_tokens += added_thread_tokens;
+
+		*self.days.entry(today_key()).or_default() += usage;
+	}
+
+	/// Records the usage of a bundle upload (not part of any conversation).
+	pub fn record_upload(&mut self, usage: &Usage) {
+		self.total += usage;
+		self.uploads += usage;
+		*self.days.entry(today_key()).or_default() += usage;
+	}
+
+	pub fn conv(&self, conv_key: &str) -> Usage {
+		self.convs
+			.get(conv_key)
+			.map(|c| c.usage.clone())
+			.unwrap_or_default()
+	}
+
+	pub fn today(&self) -> Usage {
+		self.days.get(&today_key()).cloned().unwrap_or_default()
+	}
+
+	pub fn month(&self) -> Usage {
+		let month_prefix = Local::now().format("%Y-%m-").to_string();
+		let mut usage = Usage::default();
+		for (_, day_usage) in self
+			.days
+			.iter()
+			.filter(|(day, _)| day.starts_with(&month_prefix))
+		{
+			usage += day_usage;
+		}
+		usage
+	}
+}
+
+fn today_key() -> String {
+	Local::now().format("%Y-%m-%d").to_string()
+}
+
+// endregion: --- UsageStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_usage_store_record_and_save_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join("buddy-test-usage");
+		fs::create_dir_all(&dir)?;
+		let file = dir.join("usage.json");
+		let mut store = UsageStore::default();
+
+		// -- Exec
+		store.record("thread_1", &Usage::new(100, 20, 0.5), 30);
+		store.record_upload(&Usage::new(1000, 0, 1.));
+		store.save(&file)?;
+		let store = UsageStore::load(&file)?;
+
+		// -- Check
+		assert!(!dir.join("usage.json.tmp").exists());
+		assert_eq!(store.total.total_tokens(), 1120);
+		assert_eq!(store.today().requests, 2);
+		assert_eq!(store.uploads.prompt_tokens, 1000);
+		assert_eq!(store.conv("thread_1").total_tokens(), 120);
+		assert_eq!(store.thread_tokens("thread_1"), 30);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,123 @@
+//! File watching support for `Buddy::watch`.
+//!
+//! Each watched path has its own `simple_fs` watcher (debounced, on a blocking thread),
+//! which forwards the relevant changes as `WatchTarget` to the async `Buddy::watch` loop.
+
+use crate::{Error, Result};
+use simple_fs::{watch, SEvent};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Directories whose changes are never relevant (e.g., build outputs, buddy data).
+const IGNORED_DIRS: &[&str] = &[".buddy", ".git", "target", "node_modules"];
+/// How often a watcher thread checks whether it was stopped.
+const STOP_CHECK_MS: u64 = 200;
+
+/// What a file change affects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum WatchTarget {
+	Instructions,
+	Bundle(String),
+}
+
+impl WatchTarget {
+	pub fn name(&self) -> &str {
+		match self {
+			WatchTarget::Instructions => "instructions",
+			WatchTarget::Bundle(name) => name,
+		}
+	}
+}
+
+/// The handle of a watcher thread, which stops the thread when dropped.
+pub(super) struct WatcherHandle {
+	stop: Arc<AtomicBool>,
+	_thread: JoinHandle<()>,
+}
+
+impl Drop for WatcherHandle {
+	fn drop(&mut self) {
+		// Note: Not joined, to not block the async runtime (the thread ends within
+		//       `STOP_CHECK_MS`, and the `simple_fs` watcher is dropped with it).
+		self.stop.store(true, Ordering::Relaxed);
+	}
+}
+
+/// Watches `path` on a blocking thread, and sends `target` on `tx` for each relevant change.
+///
+/// For `WatchTarget::Instructions`, `path` is the instructions file, and its directory
+/// is watched (editors often replace the file on save).
+///
+/// The thread ends when the returned handle is dropped, or the `tx` receiver is dropped.
+pub(super) fn spawn_watcher(
+	target: WatchTarget,
+	path: &Path,
+	tx: UnboundedSender<WatchTarget>,
+) -> Result<WatcherHandle> {
+	let (watch_path, file_name) = match target {
+		WatchTarget::Instructions => {
+			let dir = path.parent().unwrap_or(Path::new("."));
+			(
+				dir.to_path_buf(),
+				path.file_name().map(|n| n.to_os_string()),
+			)
+		}
+		WatchTarget::Bundle(_) => (path.to_path_buf(), None),
+	};
+	let watch_path = absolute_path(&watch_path);
+
+	let swatcher = watch(&watch_path).map_err(Error::simple_fs_at(&watch_path))?;
+
+	let stop = Arc::new(AtomicBool::new(false));
+	let thread_stop = stop.clone();
+	let thread = thread::spawn(move || {
+		let check = Duration::from_millis(STOP_CHECK_MS);
+		while !thread_stop.load(Ordering::Relaxed) {
+			let events = match swatcher.rx.recv_timeout(check) {
+				Ok(events) => events,
+				Err(RecvTimeoutError::Timeout) => continue,
+				Err(RecvTimeoutError::Disconnected) => break,
+			};
+			let is_relevant = events
+				.iter()
+				.any(|event| is_relevant(event, &watch_path, file_name.as_deref()));
+			if is_relevant && tx.send(target.clone()).is_err() {
+				break;
+			}
+		}
+	});
+
+	Ok(WatcherHandle {
+		stop,
+		_thread: thread,
+	})
+}
+
+fn is_relevant(
+	event: &SEvent,
+	watch_path: &Path,
+	file_name: Option<&std::ffi::OsStr>,
+) -> bool {
+	let path = event.spath.path();
+
+	if let Some(file_name) = file_name {
+		return path.file_name() == Some(file_name);
+	}
+
+	// Note: Only the components under the watched path are checked.
+	let rel_path = path.strip_prefix(watch_path).unwrap_or(path);
+	!rel_path.components().any(|c| match c {
+		Component::Normal(name) => IGNORED_DIRS.iter().any(|dir| name == *dir),
+		_ => false,
+	})
+}
+
+/// Returns the absolute path (the notify events have absolute paths).
+fn absolute_path(path: &Path) -> PathBuf {
+	path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
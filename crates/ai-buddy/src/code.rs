@@ -0,0 +1,194 @@
+//! Extraction of the fenced code blocks of a Markdown text (e.g., a chat response).
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+/// A fenced code block (` ``` ` or `~~~`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeBlock {
+	/// The first word of the info string (e.g., `rust` for ` ```rust `).
+	pub lang: Option<String>,
+	/// The block content, with a trailing newline.
+	pub content: String,
+}
+
+/// The Markdown parser of the chat responses.
+///
+/// Note: The renderers must use it, so that their code block numbers match
+///       `extract_code_blocks` (e.g., for `/save <n>`).
+pub fn md_parser(text: &str) -> Parser<'_> {
+	Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH)
+}
+
+/// Returns the fenced code blocks of a Markdown text, in order.
+///
+/// Notes:
+/// - The fences in list items and block quotes are included (without their indentation).
+/// - A fence indented by 4 spaces or more (at the top level) is an indented code block,
+///   and is not included.
+/// - A block not closed is taken until the end of the text.
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+	let mut blocks = Vec::new();
+	let mut current: Option<CodeBlock> = None;
+
+	for event in md_parser(text) {
+		match event {
+			Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+				let lang = info.split_whitespace().next().map(String::from);
+				current = Some(CodeBlock {
+					lang,
+					content: String::new(),
+				});
+			}
+			Event::Text(text) => {
+				if let Some(block) = current.as_mut() {
+					block.content.push_str(&text);
+				}
+			}
+			Event::End(TagEnd::CodeBlock) => {
+				if let Some(block) = current.take() {
+					blocks.push(block);
+				}
+			}
+			_ => (),
+		}
+	}
+
+	blocks
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_extract_code_blocks_simple_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "Some text\n```rust\nfn main() {}\n```\nmore\n~~~\nplain\n~~~\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 2);
+		assert_eq!(blocks[0].lang.as_deref(), Some("rust"));
+		assert_eq!(blocks[0].content, "fn main() {}\n");
+		assert_eq!(blocks[1].lang, None);
+		assert_eq!(blocks[1].content, "plain\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_indented_fence_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "- item\n\n  ```toml\n  [a]\n    b = 1\n  ```\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].content, "[a]\n  b = 1\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_non_ascii_indent_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "  ```rust\n\u{a0}x\n  ```\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].content, "\u{a0}x\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_longer_fence_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "````md\n```rust\nx\n```\n````\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].lang.as_deref(), Some("md"));
+		assert_eq!(blocks[0].content, "```rust\nx\n```\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_not_closed_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "```sh\necho hi\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].content, "echo hi\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_in_quote_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "> ```rust\n> let a = 1;\n> ```\n\n```sh\nls\n```\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 2);
+		assert_eq!(blocks[0].content, "let a = 1;\n");
+		assert_eq!(blocks[1].lang.as_deref(), Some("sh"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_indented_code_not_fenced_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "Text\n\n    ```rust\n    x\n    ```\n\n```sh\nls\n```\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].lang.as_deref(), Some("sh"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_code_blocks_inline_backticks_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "Use ``` `a` ``` inline\n";
+
+		// -- Exec
+		let blocks = extract_code_blocks(text);
+
+		// -- Check
+		assert!(blocks.is_empty());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,8 +1,11 @@
+use crate::ais::RunFailure;
 use crate::event;
+use crate::BudgetExceeded;
 use async_openai::error::OpenAIError;
-use async_openai::types::RunStatus;
 use derive_more::From;
+use serde::Serialize;
 use std::io;
+use std::path::Path;
 use tokio::sync::broadcast;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -12,6 +15,47 @@ pub enum Error {
 	// -- buddy
 	ShouldNotDeleteLocalFile(String),
 	CannotFindThreadIdForConv(String),
+	ConvNameInvalid(String),
+	BudgetExceeded(BudgetExceeded),
+	BudgetConfirmRequired(BudgetExceeded),
+	ConfigInvalid {
+		path: String,
+		cause: toml::de::Error,
+	},
+	AttachNoFiles(String),
+	AttachNotText(String),
+	AttachFileTooLarge {
+		path: String,
+		size: u64,
+		max: u64,
+	},
+	AttachTooLarge {
+		size: u64,
+		max: u64,
+	},
+	AttachTooManyFiles {
+		count: usize,
+		max: usize,
+	},
+	PatchInvalid(String),
+	PatchPathInvalid {
+		path: String,
+		reason: String,
+	},
+	PatchFileNotInBundles(String),
+	PatchFileDeletionNotSupported(String),
+	PatchHunkMismatch {
+		path: String,
+		hunk: usize,
+		line: Option<usize>,
+	},
+	PatchFileChanged(String),
+	RedactPatternInvalid {
+		pattern: String,
+		cause: regex::Error,
+	},
+	/// The bundle files with secrets (for the `block` redact policy).
+	SecretsFound(Vec<String>),
 
 	// -- ais
 	MessageImageNotSupported,
@@ -19,7 +63,7 @@ pub enum Error {
 	NoMessageFoundInMessages,
 	NoOpenAIApiKeyInEnv,
 	DeleteAllFilesRequiresAtLeastOneGlob,
-	RunError(RunStatus),
+	RunError(RunFailure),
 
 	// -- Event
 	#[from]
@@ -30,21 +74,367 @@ pub enum Error {
 	IO(io::Error),
 
 	// -- Externals
-	#[from]
-	SimpleFs(simple_fs::Error),
+	/// Note: `path` is set when the call site knows it (see `Error::simple_fs_at`).
+	SimpleFs {
+		path: Option<String>,
+		cause: simple_fs::Error,
+	},
 	#[from]
 	OpenAI(OpenAIError),
 }
 
+// region:    --- ErrorKind
+
+/// Category of an error, so that callers can react appropriately
+/// (e.g., retry on `RateLimit` or `Network`, fix the setup on `Config` or `Auth`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+	/// Invalid or missing configuration (e.g., `buddy.toml`, budget limits).
+	Config,
+	/// Missing or rejected credentials.
+	Auth,
+	/// Connection or transport failure with the AI provider.
+	Network,
+	/// Rate limit or quota reached on the AI provider.
+	RateLimit,
+	/// The AI provider failed or returned an unexpected response.
+	Provider,
+	/// Local file system or runtime failure.
+	LocalIo,
+	/// An input that cannot be used as is (e.g., a patch not matching the files).
+	Input,
+}
+
+impl Error {
+	pub fn kind(&self) -> ErrorKind {
+		match self {
+			Error::ConfigInvalid { .. }
+			| Error::ConvNameInvalid(_)
+			| Error::BudgetExceeded(_)
+			| Error::BudgetConfirmRequired(_)
+			| Error::AttachNoFiles(_)
+			| Error::AttachNotText(_)
+			| Error::AttachFileTooLarge { .. }
+			| Error::AttachTooLarge { .. }
+			| Error::AttachTooManyFiles { .. }
+			| Error::RedactPatternInvalid { .. }
+			| Error::SecretsFound(_)
+			| Error::DeleteAllFilesRequiresAtLeastOneGlob => ErrorKind::Config,
+
+			Error::NoOpenAIApiKeyInEnv => ErrorKind::Auth,
+
+			Error::RunError(failure) => match failure.code.as_deref() {
+				Some("rate_limit_exceeded") => ErrorKind::RateLimit,
+				_ => ErrorKind::Provider,
+			},
+
+			// Note: The patches come from the assistant responses, but the provider did not fail.
+			Error::PatchInvalid(_)
+			| Error::PatchPathInvalid { .. }
+			| Error::PatchFileNotInBundles(_)
+			| Error::PatchFileDeletionNotSupported(_)
+			| Error::PatchHunkMismatch { .. } => ErrorKind::Input,
+
+			Error::CannotFindThreadIdForConv(_)
+			| Error::MessageImageNotSupported
+			| Error::NoMessageInMessageObjectContent
+			| Error::NoMessageFoundInMessages => ErrorKind::Provider,
+
+			Error::ShouldNotDeleteLocalFile(_)
+			| Error::PatchFileChanged(_)
+			| Error::BoadcastSend(_)
+			| Error::IO(_)
+			| Error::SimpleFs { .. } => ErrorKind::LocalIo,
+
+			Error::OpenAI(oa_err) => match oa_err {
+				OpenAIError::Reqwest(_) | OpenAIError::StreamError(_) => {
+					ErrorKind::Network
+				}
+				OpenAIError::ApiError(api_err) => {
+					let code = api_err.code.as_ref().and_then(|c| c.as_str());
+					let typ = api_err.r#type.as_deref();
+					match (code, typ) {
+						(Some("invalid_api_key"), _)
+						| (_, Some("authentication_error")) => ErrorKind::Auth,
+						(Some("rate_limit_exceeded" | "insufficient_quota"), _)
+						| (_, Some("insufficient_quota")) => ErrorKind::RateLimit,
+						_ => ErrorKind::Provider,
+					}
+				}
+				OpenAIError::FileSaveError(_) | OpenAIError::FileReadError(_) => {
+					ErrorKind::LocalIo
+				}
+				OpenAIError::InvalidArgument(_) => ErrorKind::Config,
+				OpenAIError::JSONDeserialize(_) => ErrorKind::Provider,
+			},
+		}
+	}
+
+	/// Returns a `map_err` function adding the path context to a `simple_fs::Error`.
+	pub(crate) fn simple_fs_at(
+		path: impl AsRef<Path>,
+	) -> impl FnOnce(simple_fs::Error) -> Error {
+		let path = path.as_ref().to_string_lossy().to_string();
+		move |cause| Error::SimpleFs {
+			path: Some(path),
+			cause,
+		}
+	}
+}
+
+// endregion: --- ErrorKind
+
+// region:    --- Froms
+
+impl From<simple_fs::Error> for Error {
+	fn from(cause: simple_fs::Error) -> Self {
+		Error::SimpleFs { path: None, cause }
+	}
+}
+
+// endregion: --- Froms
+
 // region:    --- Error Boilerplate
 impl core::fmt::Display for Error {
 	fn fmt(
 		&self,
 		fmt: &mut core::fmt::Formatter,
 	) -> core::result::Result<(), core::fmt::Error> {
-		write!(fmt, "{self:?}")
+		match self {
+			// -- buddy
+			Error::ShouldNotDeleteLocalFile(file) => write!(
+				fmt,
+				"Refusing to delete '{file}' because it is not in a .buddy/ directory"
+			),
+			Error::CannotFindThreadIdForConv(thread_id) => write!(
+				fmt,
+				"The conversation thread '{thread_id}' cannot be found (it might have been deleted)"
+			),
+			Error::ConvNameInvalid(name) => write!(
+				fmt,
+				"Invalid conversation name '{name}' (only letters, digits, '-' and '_')"
+			),
+			Error::BudgetExceeded(exceeded) => {
+				write!(fmt, "Request blocked, it would exceed the {exceeded}")
+			}
+			Error::BudgetConfirmRequired(exceeded) => write!(
+				fmt,
+				"Request needs confirmation, it would exceed the {exceeded}"
+			),
+			Error::ConfigInvalid { path, cause } => {
+				write!(fmt, "Invalid config file '{path}': {cause}")
+			}
+			Error::AttachNoFiles(path_or_glob) => {
+				write!(fmt, "No files found for '{path_or_glob}'")
+			}
+			Error::AttachNotText(path) => write!(
+				fmt,
+				"Cannot inline '{path}', it is not a UTF-8 text file (see [attach] mode)"
+			),
+			Error::AttachFileTooLarge { path, size, max } => write!(
+				fmt,
+				"File '{path}' is too large to attach ({} KB, max {} KB, see [attach] max_file_kb)",
+				size / 1024,
+				max / 1024
+			),
+			Error::AttachTooLarge { size, max } => write!(
+				fmt,
+				"Attached files are too large (over {} KB, max {} KB, see [attach] max_total_kb)",
+				size / 1024,
+				max / 1024
+			),
+			Error::AttachTooManyFiles { count, max } => {
+				write!(fmt, "Too many files to attach ({count}, max {max})")
+			}
+			Error::PatchInvalid(cause) => write!(fmt, "Invalid patch: {cause}"),
+			Error::PatchPathInvalid { path, reason } => {
+				write!(fmt, "Invalid patch path '{path}' ({reason})")
+			}
+			Error::PatchFileNotInBundles(path) => write!(
+				fmt,
+				"Patch file '{path}' not found in the file_bundles src_dir(s)"
+			),
+			Error::PatchFileDeletionNotSupported(path) => {
+				write!(fmt, "Patch deletes '{path}', which is not supported")
+			}
+			Error::PatchHunkMismatch { path, hunk, line } => match line {
+				Some(line) => write!(
+					fmt,
+					"Patch hunk #{hunk} of '{path}' (line {line}) does not match the current file"
+				),
+				None => write!(
+					fmt,
+					"Patch hunk #{hunk} of '{path}' does not match the current file"
+				),
+			},
+			Error::PatchFileChanged(path) => write!(
+				fmt,
+				"File '{path}' changed since the patch was checked, check it again"
+			),
+			Error::RedactPatternInvalid { pattern, cause } => write!(
+				fmt,
+				"Invalid [redact] pattern '{pattern}': {cause}"
+			),
+			Error::SecretsFound(files) => write!(
+				fmt,
+				"Upload blocked, secrets found in {} (see [redact] policy)",
+				files.join(", ")
+			),
+
+			// -- ais
+			Error::MessageImageNotSupported => {
+				write!(fmt, "The response is an image, which is not supported yet")
+			}
+			Error::NoMessageInMessageObjectContent => {
+				write!(fmt, "The response message has no content")
+			}
+			Error::NoMessageFoundInMessages => {
+				write!(fmt, "No response message found in the conversation thread")
+			}
+			Error::NoOpenAIApiKeyInEnv => {
+				write!(fmt, "No OPENAI_API_KEY environment variable")
+			}
+			Error::DeleteAllFilesRequiresAtLeastOneGlob => {
+				write!(fmt, "Deleting files requires at least one glob")
+			}
+			Error::RunError(failure) => {
+				write!(fmt, "The assistant run ended with {failure}")
+			}
+
+			// -- Event
+			Error::BoadcastSend(_) => {
+				write!(fmt, "Cannot send the event, no event subscriber left")
+			}
+
+			// -- Std
+			Error::IO(err) => write!(fmt, "IO error: {err}"),
+
+			// -- Externals
+			Error::SimpleFs { path, cause } => {
+				let path = path.as_deref().or_else(|| simple_fs_path(cause));
+				match path {
+					Some(path) => write!(
+						fmt,
+						"File error on '{path}': {}",
+						simple_fs_reason(cause)
+					),
+					None => write!(fmt, "File error: {}", simple_fs_reason(cause)),
+				}
+			}
+			Error::OpenAI(err) => write!(fmt, "OpenAI error: {err}"),
+		}
 	}
 }
 
 impl std::error::Error for Error {}
 // endregion: --- Error Boilerplate
+
+// region:    --- simple_fs Support
+
+/// Returns the path of the simple_fs errors carrying it as a plain string.
+fn simple_fs_path(err: &simple_fs::Error) -> Option<&str> {
+	use simple_fs::Error as E;
+	match err {
+		E::PathNotUtf8(path)
+		| E::PathHasNoFileName(path)
+		| E::FileNotFound(path)
+		| E::FileHasNoParent(path)
+		| E::CantWatchPathNotFound(path)
+		| E::FailToWatch { path, .. } => Some(path),
+		_ => None,
+	}
+}
+
+/// Human readable reason of a simple_fs error.
+fn simple_fs_reason(err: &simple_fs::Error) -> String {
+	use simple_fs::Error as E;
+	// Note: Catch-all for the variants of other simple_fs versions or features.
+	#[allow(unreachable_patterns)]
+	match err {
+		E::PathNotUtf8(_) => "path is not valid UTF-8".to_string(),
+		E::PathHasNoFileName(_) => "path has no file name".to_string(),
+		E::FileNotFound(_) => "file not found".to_string(),
+		E::FileHasNoParent(_) => "file has no parent directory".to_string(),
+		E::FileCantOpen(pc) => format!("cannot open file ({})", simple_fs_cause(pc)),
+		E::FileCantRead(pc) => format!("cannot read file ({})", simple_fs_cause(pc)),
+		E::FileCantWrite(pc) => {
+			format!("cannot write file ({})", simple_fs_cause(pc))
+		}
+		E::FileCantCreate(pc) => {
+			format!("cannot create file ({})", simple_fs_cause(pc))
+		}
+		E::DirCantCreateAll(pc) => {
+			format!("cannot create directory ({})", simple_fs_cause(pc))
+		}
+		E::PathNotValidForPath(pc) => {
+			format!("invalid path ({})", simple_fs_cause(pc))
+		}
+		E::GlobCantNew { glob, cause } => format!("invalid glob '{glob}': {cause}"),
+		E::GlobSetCantBuild { globs, cause } => {
+			format!("invalid globs '{}': {cause}", globs.join("', '"))
+		}
+		E::FailToWatch { cause, .. } => format!("cannot watch: {cause}"),
+		E::CantWatchPathNotFound(_) => "cannot watch, path not found".to_string(),
+		E::JsonCantRead(pc) => format!("cannot read JSON ({})", simple_fs_cause(pc)),
+		E::JsonCantWrite(pc) => {
+			format!("cannot write JSON ({})", simple_fs_cause(pc))
+		}
+		E::TomlCantRead(pc) => format!("cannot read TOML ({})", simple_fs_cause(pc)),
+		E::TomlCantWrite(pc) => {
+			format!("cannot write TOML ({})", simple_fs_cause(pc))
+		}
+		_ => err.to_string(),
+	}
+}
+
+/// The cause message of a simple_fs `PathAndCause` (e.g., `No such file or directory`).
+///
+/// Note: The `PathAndCause` fields are private and it has no Display, so the message
+///       is taken from its Debug (the io `message`, or the serde `Error("...")` text).
+fn simple_fs_cause(pc: &impl std::fmt::Debug) -> String {
+	let debug = format!("{pc:?}");
+	let between = |start: &str, end: &str| {
+		let from = debug.find(start)? + start.len();
+		let len = debug[from..].find(end)?;
+		Some(debug[from..from + len].to_string())
+	};
+	between("message: \"", "\"")
+		.or_else(|| between("Error(\"", "\""))
+		.or_else(|| between("kind: ", " "))
+		.map(|cause| cause.trim_end_matches(',').to_string())
+		.unwrap_or(debug)
+}
+
+// endregion: --- simple_fs Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_simple_fs_reason_io_cause_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let path = std::env::temp_dir().join("buddy-test-error-missing/file.json");
+
+		// -- Exec
+		let Err(err) = simple_fs::load_json::<serde_json::Value>(&path) else {
+			return Err("should fail".into());
+		};
+		let reason = simple_fs_reason(&err);
+
+		// -- Check
+		// Note: The io message depends on the OS (e.g., `No such file or directory`).
+		assert!(reason.starts_with("cannot open file ("), "{reason}");
+		assert!(!reason.contains("PathAndCause"), "{reason}");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
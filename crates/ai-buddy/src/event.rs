@@ -3,10 +3,12 @@ pub use crate::buddy::BuddyEvent;
 
 use crate::Result;
 use derive_more::From;
+use serde::Serialize;
 use std::sync::Arc;
 use tokio::sync::broadcast::{self, Receiver, Sender};
 
-#[derive(Debug, Clone, From)]
+#[derive(Debug, Clone, From, Serialize)]
+#[serde(tag = "source", content = "event", rename_all = "snake_case")]
 pub enum Event {
 	Ais(AisEvent),
 	Buddy(BuddyEvent),
@@ -2,11 +2,13 @@
 
 mod ais;
 mod buddy;
+pub mod code;
 mod error;
 pub mod event;
+pub mod patch;
 mod utils;
 
-pub use self::error::{Error, Result};
+pub use self::error::{Error, ErrorKind, Result};
 
 pub use crate::buddy::*;
 
@@ -0,0 +1,611 @@
+//! Parsing and applying of unified diffs (e.g., proposed by the assistant).
+//!
+//! The parser is lenient with the usual mistakes of generated diffs: a hunk can be
+//! longer than its header line counts, the hunk headers can omit the line numbers
+//! (`@@ ... @@`), and a hunk can apply at another line than its header says (nearest
+//! match first).
+//!
+//! The applied content keeps the line endings (LF or CRLF) and the context lines of
+//! the file, only the removed and added lines come from the patch.
+//!
+//! Note: Within the header line counts, a `--- `/`+++ ` line is a removed/added line
+//!       (e.g., the removal of a `-- comment` SQL line), not a file header.
+
+use crate::{Error, Result};
+
+// region:    --- Types
+
+/// The patch of one file.
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+	/// `None` for a new file (`--- /dev/null`).
+	pub old_path: Option<String>,
+	/// `None` for a deleted file (`+++ /dev/null`).
+	pub new_path: Option<String>,
+	pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+	/// The path of the patched file (the new path, or the old one for a deleted file).
+	pub fn path(&self) -> &str {
+		self.new_path
+			.as_deref()
+			.or(self.old_path.as_deref())
+			.unwrap_or_default()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+	/// 1-based start line in the old file, when the header has it.
+	pub old_start: Option<usize>,
+	pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+	Context(String),
+	Add(String),
+	Remove(String),
+}
+
+impl Hunk {
+	/// The lines the hunk expects in the old file.
+	fn old_lines(&self) -> Vec<&str> {
+		self.lines
+			.iter()
+			.filter_map(|line| match line {
+				HunkLine::Context(l) | HunkLine::Remove(l) => Some(l.as_str()),
+				HunkLine::Add(_) => None,
+			})
+			.collect()
+	}
+
+	/// The lines the hunk puts in the new file.
+	fn new_lines(&self) -> Vec<&str> {
+		self.lines
+			.iter()
+			.filter_map(|line| match line {
+				HunkLine::Context(l) | HunkLine::Add(l) => Some(l.as_str()),
+				HunkLine::Remove(_) => None,
+			})
+			.collect()
+	}
+}
+
+// endregion: --- Types
+
+// region:    --- Parse
+
+/// Parses a unified diff, with one `FilePatch` per `---`/`+++` file header.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>> {
+	let mut patches: Vec<FilePatch> = Vec::new();
+	let mut lines = text.lines().enumerate().peekable();
+
+	while let Some((idx, line)) = lines.next() {
+		if let Some(old) = line.strip_prefix("--- ") {
+			let new = match lines.next() {
+				Some((_, next)) if next.starts_with("+++ ") => &next[4..],
+				_ => {
+					return Err(Error::PatchInvalid(format!(
+						"line {}: '---' without a '+++' line",
+						idx + 1
+					)))
+				}
+			};
+			patches.push(FilePatch {
+				old_path: parse_path(old),
+				new_path: parse_path(new),
+				hunks: Vec::new(),
+			});
+		} else if line.starts_with("@@") {
+			let Some(patch) = patches.last_mut() else {
+				return Err(Error::PatchInvalid(format!(
+					"line {}: hunk without a '---'/'+++' file header",
+					idx + 1
+				)));
+			};
+
+			let mut hunk = Hunk {
+				old_start: parse_range(line, '-').map(|(start, _)| start),
+				lines: Vec::new(),
+			};
+			// The lines left per the header counts (none for a `@@ ... @@` header).
+			let (mut old_left, mut new_left) =
+				match (parse_range(line, '-'), parse_range(line, '+')) {
+					(Some((_, old_count)), Some((_, new_count))) => {
+						(old_count, new_count)
+					}
+					_ => (0, 0),
+				};
+			// The number of hunk lines within the header counts.
+			let mut counted = 0;
+			while let Some((_, line)) = lines.peek() {
+				if line.starts_with('\\') {
+					// "\ No newline at end of file"
+					lines.next();
+					continue;
+				}
+				let in_counts = old_left > 0 || new_left > 0;
+				let Some(hunk_line) = parse_hunk_line(line, in_counts) else {
+					break;
+				};
+				match hunk_line {
+					HunkLine::Context(_) => {
+						old_left = old_left.saturating_sub(1);
+						new_left = new_left.saturating_sub(1);
+					}
+					HunkLine::Remove(_) => old_left = old_left.saturating_sub(1),
+					HunkLine::Add(_) => new_left = new_left.saturating_sub(1),
+				}
+				hunk.lines.push(hunk_line);
+				if in_counts {
+					counted = hunk.lines.len();
+				}
+				lines.next();
+			}
+
+			// Trailing empty lines (after the counts) are the end of the text, not context.
+			while hunk.lines.len() > counted
+				&& hunk.lines.last() == Some(&HunkLine::Context(String::new()))
+			{
+				hunk.lines.pop();
+			}
+			patch.hunks.push(hunk);
+		}
+		// Note: Other lines (e.g., `diff --git`, `index`, comments) are ignored.
+	}
+
+	patches.retain(|p| !p.hunks.is_empty());
+	if patches.is_empty() {
+		return Err(Error::PatchInvalid("no file hunks found".to_string()));
+	}
+
+	Ok(patches)
+}
+
+/// Returns the path of a `---`/`+++` header (without the `a/`, `b/` prefixes),
+/// or `None` for `/dev/null`.
+fn parse_path(header: &str) -> Option<String> {
+	// Note: The header can have a timestamp after a tab.
+	let path = header.split('\t').next().unwrap_or_default().trim();
+	if path == "/dev/null" {
+		return None;
+	}
+	let path = path
+		.strip_prefix("a/")
+		.or_else(|| path.strip_prefix("b/"))
+		.unwrap_or(path);
+	Some(path.to_string())
+}
+
+/// Returns the line of a hunk, or `None` at the end of the hunk.
+///
+/// Out of the header counts (`in_counts` false), the `--- `/`+++ ` lines are file headers.
+fn parse_hunk_line(line: &str, in_counts: bool) -> Option<HunkLine> {
+	let hunk_line = match line.chars().next() {
+		Some(' ') => HunkLine::Context(line[1..].to_string()),
+		Some('+') if in_counts || !line.starts_with("+++ ") => {
+			HunkLine::Add(line[1..].to_string())
+		}
+		Some('-') if in_counts || !line.starts_with("--- ") => {
+			HunkLine::Remove(line[1..].to_string())
+		}
+		// Note: Generated diffs often lose the space of the empty context lines.
+		None => HunkLine::Context(String::new()),
+		_ => return None,
+	};
+	Some(hunk_line)
+}
+
+/// Returns the `(start, count)` of the `-` (old) or `+` (new) range of a
+/// `@@ -l,s +l,s @@` header (the count is 1 when omitted).
+fn parse_range(header: &str, sign: char) -> Option<(usize, usize)> {
+	let range = header
+		.split_whitespace()
+		.skip(1)
+		.take(2)
+		.find_map(|part| part.strip_prefix(sign))?;
+	let (start, count) = match range.split_once(',') {
+		Some((start, count)) => (start, count.parse().ok()?),
+		None => (range, 1),
+	};
+	Some((start.parse().ok()?, count))
+}
+
+// endregion: --- Parse
+
+// region:    --- Apply
+
+/// Applies the hunks of `patch` to `content`, and returns the new content.
+///
+/// Each hunk must match the content exactly (trailing whitespace aside), at its line
+/// or the nearest one, otherwise `Error::PatchHunkMismatch` is returned.
+pub fn apply_patch(content: &str, patch: &FilePatch) -> Result<String> {
+	let mut lines: Vec<String> = content.lines().map(String::from).collect();
+	// Offset between the old line numbers and the current lines.
+	let mut offset: isize = 0;
+	// Hunks apply in order, so a hunk cannot match before the previous one.
+	let mut min_idx = 0;
+
+	for (hunk_idx, hunk) in patch.hunks.iter().enumerate() {
+		let old_lines = hunk.old_lines();
+		let new_lines = hunk.new_lines();
+
+		let expected = hunk
+			.old_start
+			.map(|start| {
+				// Note: A hunk without old lines inserts after its start line (`-l,0`).
+				let idx = if old_lines.is_empty() {
+					start
+				} else {
+					start.saturating_sub(1)
+				};
+				(idx as isize + offset).max(0) as usize
+			})
+			.unwrap_or(min_idx)
+			.max(min_idx);
+
+		let idx =
+			find_lines(&lines, &old_lines, expected, min_idx).ok_or_else(|| {
+				Error::PatchHunkMismatch {
+					path: patch.path().to_string(),
+					hunk: hunk_idx + 1,
+					line: hunk.old_start,
+				}
+			})?;
+
+		// Note: The context lines are kept from the file (e.g., with their trailing whitespace).
+		let mut old_idx = idx;
+		let mut replacement: Vec<String> = Vec::with_capacity(new_lines.len());
+		for line in hunk.lines.iter() {
+			match line {
+				HunkLine::Context(_) => {
+					replacement.push(lines[old_idx].clone());
+					old_idx += 1;
+				}
+				HunkLine::Remove(_) => old_idx += 1,
+				HunkLine::Add(line) => replacement.push(line.clone()),
+			}
+		}
+		lines.splice(idx..old_idx, replacement);
+		offset += new_lines.len() as isize - old_lines.len() as isize;
+		min_idx = idx + new_lines.len();
+	}
+
+	let eol = if content.contains("\r\n") {
+		"\r\n"
+	} else {
+		"\n"
+	};
+	let mut res = lines.join(eol);
+	if !res.is_empty() && (content.is_empty() || content.ends_with('\n')) {
+		res.push_str(eol);
+	}
+	Ok(res)
+}
+
+/// Returns the index where `needle` matches in `lines` (at or after `min_idx`),
+/// the nearest to `expected` first.
+fn find_lines(
+	lines: &[String],
+	needle: &[&str],
+	expected: usize,
+	min_idx: usize,
+) -> Option<usize> {
+	if needle.is_empty() {
+		return Some(expected.min(lines.len()));
+	}
+	let last_idx = lines.len().checked_sub(needle.len())?;
+
+	let matches_at = |idx: usize| {
+		lines[idx..idx + needle.len()]
+			.iter()
+			.zip(needle)
+			.all(|(line, expected)| line.trim_end() == expected.trim_end())
+	};
+
+	let expected = expected.min(last_idx);
+	(0..=last_idx).find_map(|distance| {
+		let after = expected + distance;
+		let before = expected.checked_sub(distance).filter(|i| *i >= min_idx);
+		if after <= last_idx && after >= min_idx && matches_at(after) {
+			Some(after)
+		} else {
+			before.filter(|i| matches_at(*i))
+		}
+	})
+}
+
+// endregion: --- Apply
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_parse_patch_remove_dash_dash_line_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "-- old comment\nSELECT 1;\n";
+		let text = "\
+--- a/q.sql
++++ b/q.sql
+@@ -1,2 +1,2 @@
+--- old comment
++-- new comment
+ SELECT 1;
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(patches.len(), 1);
+		assert_eq!(res, "-- new comment\nSELECT 1;\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_parse_patch_multi_files_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "\
+diff --git a/src/a.rs b/src/a.rs
+index 123..456 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1 +1 @@
+-a
++b
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++fn new() {}
++
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+
+		// -- Check
+		assert_eq!(patches.len(), 2);
+		assert_eq!(patches[0].path(), "src/a.rs");
+		assert_eq!(patches[0].hunks[0].old_start, Some(1));
+		assert_eq!(patches[1].old_path, None);
+		assert_eq!(patches[1].path(), "src/new.rs");
+		// The empty added line is within the counts, so it is kept.
+		assert_eq!(
+			patches[1].hunks[0].lines,
+			vec![
+				HunkLine::Add("fn new() {}".to_string()),
+				HunkLine::Add(String::new())
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_parse_patch_no_file_header_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "@@ -1 +1 @@\n-a\n+b\n";
+
+		// -- Exec
+		let res = parse_patch(text);
+
+		// -- Check
+		assert!(matches!(res, Err(crate::Error::PatchInvalid(_))));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_offset_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// The header says line 2, but two lines were added at the top since.
+		let content = "x\ny\na\nb\nc\n";
+		let text = "\
+--- a/f.txt
++++ b/f.txt
+@@ -2,2 +2,2 @@
+ b
+-c
++C
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "x\ny\na\nb\nC\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_nearest_match_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// `b` is at lines 1 and 4, the hunk header points to line 5.
+		let content = "b\n1\n2\nb\n3\n";
+		let text = "--- a/f\n+++ b/f\n@@ -5,1 +5,1 @@\n-b\n+B\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "b\n1\n2\nB\n3\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_multi_hunks_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "1\n2\n3\n4\n5\n6\n";
+		let text = "\
+--- a/f
++++ b/f
+@@ -1,2 +1,3 @@
+ 1
++1.5
+ 2
+@@ -5,2 +6,1 @@
+ 5
+-6
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "1\n1.5\n2\n3\n4\n5\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_pure_add_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "a\nb\n";
+		let text = "--- a/f\n+++ b/f\n@@ -1,0 +2,1 @@\n+inserted\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "a\ninserted\nb\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_new_file_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "\
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++fn main() {
++}
+\\ No newline at end of file
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch("", &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "fn main() {\n}\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_lenient_header_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// No line numbers, a lost space on the empty context line, and trailing
+		// whitespace differences.
+		let content = "fn a() {\n\n\tlet x = 1;  \n}\n";
+		let text = "\
+--- a/f.rs
++++ b/f.rs
+@@ ... @@
+ fn a() {
+
+-\tlet x = 1;
++\tlet x = 2;
+ }
+
+";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "fn a() {\n\n\tlet x = 2;\n}\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_crlf_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "a\r\nb\r\nc\r\n";
+		let text = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "a\r\nB\r\nc\r\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_keep_context_lines_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// The patch context lost the trailing whitespace of `a` and `c`.
+		let content = "a  \nb\nc\t\n";
+		let text = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "a  \nB\nc\t\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_longer_than_counts_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// The header counts are too small (a usual mistake of generated diffs).
+		let content = "a\nb\nc\n";
+		let text = "--- a/f\n+++ b/f\n@@ -1,1 +1,1 @@\n a\n-b\n+B\n c\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0])?;
+
+		// -- Check
+		assert_eq!(res, "a\nB\nc\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_patch_mismatch_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "a\nb\n";
+		let text = "--- a/f\n+++ b/f\n@@ -1,1 +1,1 @@\n-z\n+Z\n";
+
+		// -- Exec
+		let patches = parse_patch(text)?;
+		let res = apply_patch(content, &patches[0]);
+
+		// -- Check
+		assert!(matches!(
+			res,
+			Err(crate::Error::PatchHunkMismatch { hunk: 1, .. })
+		));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,24 +1,59 @@
-use crate::Result;
-use simple_fs::{get_buf_reader, SFile, SPath};
-use std::fs::File;
-use std::io::Write;
-use std::io::{BufRead, BufWriter};
-
-pub fn bundle_to_file(files: Vec<SFile>, dst_file: &SPath) -> Result<()> {
-	let mut writer = BufWriter::new(File::create(dst_file)?);
-
-	for file in files {
-		let reader = get_buf_reader(&file)?;
-
-		writeln!(writer, "\n// ==== file path: {file}\n")?;
+/// The line comment syntax of a file type (e.g., `//`, `#`, or `<!-- ... -->`).
+#[derive(Debug, Clone, Copy)]
+pub struct CommentSyntax {
+	start: &'static str,
+	end: Option<&'static str>,
+}
 
-		for line in reader.lines() {
-			let line = line?;
-			writeln!(writer, "{}", line)?;
+impl CommentSyntax {
+	/// Returns the text as a one-line comment.
+	pub fn line(&self, text: &str) -> String {
+		match self.end {
+			Some(end) => format!("{} {text} {end}", self.start),
+			None => format!("{} {text}", self.start),
 		}
-		writeln!(writer, "\n\n")?;
 	}
-	writer.flush()?;
+}
+
+/// Returns the comment syntax for a file extension (`//` when unknown).
+pub fn comment_syntax(ext: &str) -> CommentSyntax {
+	let (start, end) = match ext.to_lowercase().as_str() {
+		"md" | "html" | "htm" | "xml" | "svg" | "vue" => ("<!--", Some("-->")),
+		"css" | "scss" => ("/*", Some("*/")),
+		"py" | "sh" | "bash" | "zsh" | "rb" | "toml" | "yaml" | "yml" | "txt"
+		| "r" | "pl" | "ini" | "conf" | "dockerfile" | "mk" => ("#", None),
+		"sql" | "lua" | "hs" => ("--", None),
+		_ => ("//", None),
+	};
+	CommentSyntax { start, end }
+}
 
-	Ok(())
+/// Returns the language name for a file extension (the extension when unknown).
+pub fn lang_name(ext: &str) -> String {
+	let lang = match ext.to_lowercase().as_str() {
+		"rs" => "rust",
+		"ts" | "tsx" => "typescript",
+		"js" | "jsx" | "mjs" | "cjs" => "javascript",
+		"py" => "python",
+		"rb" => "ruby",
+		"go" => "go",
+		"java" => "java",
+		"kt" => "kotlin",
+		"c" | "h" => "c",
+		"cpp" | "cc" | "hpp" => "cpp",
+		"cs" => "csharp",
+		"swift" => "swift",
+		"sh" | "bash" | "zsh" => "shell",
+		"md" => "markdown",
+		"html" | "htm" => "html",
+		"css" => "css",
+		"scss" => "scss",
+		"json" => "json",
+		"toml" => "toml",
+		"yaml" | "yml" => "yaml",
+		"sql" => "sql",
+		"" => "text",
+		other => return other.to_string(),
+	};
+	lang.to_string()
 }
@@ -1,5 +1,8 @@
 // region:    --- Modules
 
 pub mod files;
+pub mod redact;
+pub mod rust_outline;
+pub mod tokens;
 
 // endregion: --- Modules
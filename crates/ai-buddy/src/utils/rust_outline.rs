@@ -0,0 +1,207 @@
+//! Outline of a Rust source file (for the `outline` bundle mode).
+//!
+//! The items and their doc comments are kept, the long function bodies are elided,
+//! and the private `use` items and `#[cfg(test)]` modules are removed.
+
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, Attribute, Block, Ident, Item, Visibility};
+
+/// The macro marking an elided body, printed as a `// ...` comment.
+const ELIDED_MACRO: &str = "__buddy_elided!()";
+
+/// Returns the outline of a Rust source file, or `None` if it cannot be parsed.
+///
+/// The function bodies over `max_body_lines` (braces included) are elided.
+pub fn rust_outline(content: &str, max_body_lines: usize) -> Option<String> {
+	let mut file = syn::parse_file(content).ok()?;
+	Outliner { max_body_lines }.visit_file_mut(&mut file);
+
+	let outline = prettyplease::unparse(&file);
+	Some(outline.replace(ELIDED_MACRO, "// ..."))
+}
+
+struct Outliner {
+	max_body_lines: usize,
+}
+
+impl Outliner {
+	fn elide(&self, block: &mut Block) {
+		let span = block.brace_token.span;
+		let lines = span.close().end().line - span.open().start().line + 1;
+		if lines > self.max_body_lines {
+			*block = parse_quote!({ __buddy_elided!() });
+		}
+	}
+}
+
+impl VisitMut for Outliner {
+	fn visit_file_mut(&mut self, file: &mut syn::File) {
+		file.items.retain(is_outlined);
+		visit_mut::visit_file_mut(self, file);
+	}
+
+	fn visit_item_mod_mut(&mut self, item_mod: &mut syn::ItemMod) {
+		if let Some((_, items)) = item_mod.content.as_mut() {
+			items.retain(is_outlined);
+		}
+		visit_mut::visit_item_mod_mut(self, item_mod);
+	}
+
+	// Note: The bodies are not visited (their nested items are not part of the outline).
+	fn visit_item_fn_mut(&mut self, item_fn: &mut syn::ItemFn) {
+		self.elide(&mut item_fn.block);
+	}
+
+	fn visit_impl_item_fn_mut(&mut self, impl_fn: &mut syn::ImplItemFn) {
+		self.elide(&mut impl_fn.block);
+	}
+
+	fn visit_trait_item_fn_mut(&mut self, trait_fn: &mut syn::TraitItemFn) {
+		if let Some(block) = trait_fn.default.as_mut() {
+			self.elide(block);
+		}
+	}
+}
+
+fn is_outlined(item: &Item) -> bool {
+	match item {
+		Item::Use(item_use) => !matches!(item_use.vis, Visibility::Inherited),
+		Item::Mod(item_mod) => !is_cfg_test(&item_mod.attrs),
+		_ => true,
+	}
+}
+
+fn is_cfg_test(attrs: &[Attribute]) -> bool {
+	attrs.iter().any(|attr| {
+		attr.path().is_ident("cfg")
+			&& attr
+				.parse_args::<Ident>()
+				.is_ok_and(|ident| ident == "test")
+	})
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_rust_outline_elide_long_bodies_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = r#"
+/// Short one.
+pub fn short() -> u32 {
+	1
+}
+
+/// Long one.
+pub fn long() -> u32 {
+	let a = 1;
+	let b = 2;
+	let c = 3;
+	a + b + c
+}
+
+struct Foo;
+
+impl Foo {
+	fn long(&self) {
+		let a = 1;
+		let b = 2;
+		let c = a + b;
+		println!("{c}");
+	}
+}
+"#;
+
+		// -- Exec
+		let outline = rust_outline(content, 5).ok_or("should parse")?;
+
+		// -- Check
+		assert!(
+			outline.contains("/// Short one.\npub fn short() -> u32 {\n    1\n}")
+		);
+		assert!(
+			outline.contains("/// Long one.\npub fn long() -> u32 {\n    // ...\n}")
+		);
+		assert!(outline.contains("    fn long(&self) {\n        // ...\n    }"));
+		assert!(!outline.contains("let a"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_rust_outline_remove_test_mods_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = r#"
+pub fn run() {}
+
+mod inner {
+	pub fn helper() {}
+
+	#[cfg(test)]
+	mod inner_tests {}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_run() {}
+}
+"#;
+
+		// -- Exec
+		let outline = rust_outline(content, 5).ok_or("should parse")?;
+
+		// -- Check
+		assert!(outline.contains("pub fn run() {}"));
+		assert!(outline.contains("pub fn helper() {}"));
+		assert!(!outline.contains("tests"));
+		assert!(!outline.contains("#[cfg(test)]"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_rust_outline_remove_private_uses_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = r#"
+use std::fs;
+pub use crate::error::{Error, Result};
+pub(crate) use crate::utils::files;
+
+pub fn run() {}
+"#;
+
+		// -- Exec
+		let outline = rust_outline(content, 5).ok_or("should parse")?;
+
+		// -- Check
+		assert!(!outline.contains("use std::fs;"));
+		assert!(outline.contains("pub use crate::error::{Error, Result};"));
+		assert!(outline.contains("pub(crate) use crate::utils::files;"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_rust_outline_unparsable_none_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		// Note: The bundle falls back to the raw source when there is no outline.
+		let content = "pub fn broken( {";
+
+		// -- Exec
+		let outline = rust_outline(content, 5);
+
+		// -- Check
+		assert!(outline.is_none());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,12 @@
+//! Local token estimation.
+//!
+//! The OpenAI Assistant run objects (as exposed by async-openai 0.18) do not carry the usage,
+//! so the tokens are estimated locally with the common "~4 characters per token" heuristic.
+
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// Returns the approximate number of tokens for a text.
+pub fn approx_tokens(text: &str) -> u64 {
+	let chars = text.chars().count() as u64;
+	chars.div_ceil(CHARS_PER_TOKEN)
+}